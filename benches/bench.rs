@@ -235,6 +235,54 @@ fn bench_fetching(b: &mut Bencher) {
     })
 }
 
+#[bench]
+fn bench_fetching_slots(b: &mut Bencher) {
+    let mut world = World::empty();
+
+    let delta = world.insert_slot(DeltaTime(0.05));
+    world.insert_slot(VecStorage::new(Mass(10.0)));
+    let pos = world.insert_slot(VecStorage::new(Pos(Vec3::new(0.0, 0.0, 0.0))));
+    let spring = world.insert_slot(VecStorage::new(Spring {
+        constant: 2.0,
+        connection_to: 0,
+        rest: 1.0,
+    }));
+
+    b.iter(|| {
+        for _ in 0..100 {
+            black_box(world.fetch_slot(delta));
+            black_box(world.fetch_slot(pos));
+            black_box(world.fetch_slot(spring));
+        }
+    })
+}
+
+/// A system small enough for fetching its data to dominate.
+#[bench]
+fn bench_dispatch_small_system(b: &mut Bencher) {
+    struct Step;
+
+    impl<'a> System<'a> for Step {
+        type SystemData = (ReadExpect<'a, DeltaTime>, WriteExpect<'a, PosStorage>);
+
+        fn run(&mut self, (delta, mut pos): Self::SystemData) {
+            pos[0].0.x += delta.0;
+        }
+    }
+
+    let mut dispatcher = DispatcherBuilder::new().with(Step, "step", &[]).build();
+
+    let mut world = World::empty();
+    world.insert(DeltaTime(0.05));
+    world.insert(VecStorage::new(Pos(Vec3::new(0.0, 0.0, 0.0))));
+
+    b.iter(|| {
+        for _ in 0..100 {
+            dispatcher.dispatch_seq(&world);
+        }
+    })
+}
+
 #[bench]
 fn bench_indirection_refs(b: &mut Bencher) {
     use shred::cell::{AtomicRef, AtomicRefCell};
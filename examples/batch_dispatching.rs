@@ -18,7 +18,9 @@
 //! This is done by defining `CustomBatchControllerSystem` which executes its
 //! inner `System`s three times.
 
-use shred::{BatchController, Dispatcher, DispatcherBuilder, Read, System, World, Write};
+use shred::{
+    BatchController, BatchFetch, BatchWorld, DispatcherBuilder, Read, System, World, Write,
+};
 use std::{thread::sleep, time::Duration};
 
 fn main() {
@@ -97,19 +99,21 @@ impl<'a> System<'a> for BuyTomatoSystem {
 pub struct CustomBatchControllerSystem;
 
 impl<'a, 'b, 'c> BatchController<'a, 'b, 'c> for CustomBatchControllerSystem {
-    // Leaving `BatchBuilderData` to `()` would make the dispatcher to panic since
-    // the run function will fetch the `TomatoStore` like the `SayHelloSystem`
-    // does. type BatchSystemData = ();
+    // The `TomatoStore` is fetched for the controller, like it is for the
+    // `SayHelloSystem`.
     type BatchSystemData = Read<'c, TomatoStore>;
 
-    fn run(&mut self, world: &World, dispatcher: &mut Dispatcher<'a, 'b>) {
-        {
-            // The scope is used to unload the resource before dispatching inner systems.
-            let _ts = world.fetch::<TomatoStore>();
-        }
+    fn run(
+        &mut self,
+        data: BatchFetch<'_, Self::BatchSystemData>,
+        mut world: BatchWorld<'_, 'a, 'b, 'c>,
+    ) {
+        // The resource has to be dropped before dispatching inner systems.
+        drop(data);
+
         println!("Batch execution");
         for _i in 0..3 {
-            dispatcher.dispatch(world);
+            world.dispatch();
         }
     }
 }
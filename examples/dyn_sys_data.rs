@@ -12,8 +12,8 @@ extern crate shred;
 use ahash::AHashMap as HashMap;
 
 use shred::{
-    Accessor, AccessorCow, CastFrom, DispatcherBuilder, DynamicSystemData, MetaTable, Read,
-    Resource, ResourceId, System, SystemData, World,
+    Accessor, AccessorCow, DispatcherBuilder, DynamicSystemData, MetaTable, Read, Resource,
+    ResourceId, System, SystemData, World, cast_from,
     cell::{AtomicRef, AtomicRefMut},
 };
 
@@ -96,14 +96,7 @@ trait Reflection {
 }
 
 // necessary for `MetaTable`
-unsafe impl<T> CastFrom<T> for dyn Reflection
-where
-    T: Reflection + 'static,
-{
-    fn cast(t: *mut T) -> *mut Self {
-        t
-    }
-}
+cast_from!(dyn Reflection);
 
 type ReflectionTable = MetaTable<dyn Reflection>;
 
@@ -73,25 +73,35 @@ fn impl_system_data(ast: &DeriveInput) -> proc_macro2::TokenStream {
 
             fn reads() -> Vec<shred::ResourceId> {
                 let mut r = Vec::new();
-
-                #( {
-                        let mut reads = <#tys as shred::SystemData> :: reads();
-                        r.append(&mut reads);
-                    } )*
+                <Self as shred::SystemData> :: extend_reads(&mut r);
 
                 r
             }
 
             fn writes() -> Vec<shred::ResourceId> {
                 let mut r = Vec::new();
-
-                #( {
-                        let mut writes = <#tys as shred::SystemData> :: writes();
-                        r.append(&mut writes);
-                    } )*
+                <Self as shred::SystemData> :: extend_writes(&mut r);
 
                 r
             }
+
+            fn extend_reads<__E: Extend<shred::ResourceId>>(reads: &mut __E) {
+                #(
+                    <#tys as shred::SystemData> :: extend_reads(reads);
+                )*
+            }
+
+            fn extend_writes<__E: Extend<shred::ResourceId>>(writes: &mut __E) {
+                #(
+                    <#tys as shred::SystemData> :: extend_writes(writes);
+                )*
+            }
+
+            fn extend_accumulates<__E: Extend<shred::ResourceId>>(accumulates: &mut __E) {
+                #(
+                    <#tys as shred::SystemData> :: extend_accumulates(accumulates);
+                )*
+            }
         }
     }
 }
@@ -1,12 +1,15 @@
 use std::{
     borrow::Borrow,
-    sync::{Arc, RwLock, mpsc},
+    sync::{Mutex, mpsc},
 };
 
 use crate::{
     dispatch::{
         dispatcher::{ThreadLocal, ThreadPoolWrapper},
+        error::{self, DispatchErrors},
+        observer::{CatchPanics, Hooks, Instruments},
         stage::Stage,
+        util::SharedSlot,
     },
     world::World,
 };
@@ -16,10 +19,16 @@ pub fn new_async<'a, R>(
     world: R,
     stages: Vec<Stage<'static>>,
     thread_local: ThreadLocal<'a>,
-    thread_pool: Arc<RwLock<ThreadPoolWrapper>>,
+    thread_pool: SharedSlot<ThreadPoolWrapper>,
+    instruments: Instruments,
 ) -> AsyncDispatcher<'a, R> {
     AsyncDispatcher {
-        data: Data::Inner(Inner { world, stages }),
+        data: Data::Inner(Inner {
+            world,
+            stages,
+            instruments,
+            errors: Default::default(),
+        }),
         thread_local,
         thread_pool,
     }
@@ -29,7 +38,7 @@ pub fn new_async<'a, R>(
 pub struct AsyncDispatcher<'a, R> {
     data: Data<R>,
     thread_local: ThreadLocal<'a>,
-    thread_pool: Arc<RwLock<ThreadPoolWrapper>>,
+    thread_pool: SharedSlot<ThreadPoolWrapper>,
 }
 
 impl<R> AsyncDispatcher<'_, R>
@@ -51,7 +60,7 @@ where
         }
 
         for sys in &mut self.thread_local {
-            sys.setup(world);
+            sys.system.setup(world);
         }
     }
 
@@ -64,15 +73,37 @@ where
         let (snd, mut inner) = self.data.sender();
 
         self.thread_pool
-            .read()
-            .unwrap()
-            .as_ref()
+            .read(Clone::clone)
             .unwrap()
             .spawn(move || {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::info_span!("dispatch").entered();
+
                 let world: &World = inner.world.borrow();
+                let stages = &mut inner.stages;
+                let trace = inner.instruments.tracer.begin_frame();
+                let panics = Mutex::default();
+                let hooks = Hooks {
+                    observers: &inner.instruments.observers,
+                    panics: CatchPanics::new(inner.instruments.panic_policy, &panics),
+                    trace: trace.as_ref(),
+                    unchecked_fetch: inner.instruments.unchecked_fetch,
+                };
 
-                for stage in &mut inner.stages {
-                    stage.execute(world);
+                hooks.dispatch(|| {
+                    for (index, stage) in stages.iter_mut().enumerate() {
+                        hooks.stage(index, || stage.execute(world, hooks, index));
+                    }
+                });
+
+                // panics are propagated by `wait`, as panicking here would
+                // abort the process
+                inner.errors = DispatchErrors {
+                    panics: panics.into_inner().unwrap(),
+                    ..Default::default()
+                };
+                for sink in &inner.instruments.error_sinks {
+                    error::drain_sink(sink, world, &mut inner.errors);
                 }
 
                 let _ = snd.send(inner);
@@ -82,11 +113,29 @@ where
     /// Waits for all the asynchronously dispatched systems to finish
     /// and executes thread local systems (if there are any).
     pub fn wait(&mut self) {
-        let world = self.data.inner().world.borrow();
+        let inner = self.data.inner();
+        let world = inner.world.borrow();
+        let trace = inner.instruments.tracer.current_frame();
+        let panics = Mutex::default();
+        let hooks = Hooks {
+            observers: &inner.instruments.observers,
+            panics: CatchPanics::new(inner.instruments.panic_policy, &panics),
+            trace: trace.as_ref(),
+            unchecked_fetch: inner.instruments.unchecked_fetch,
+        };
 
         for sys in &mut self.thread_local {
-            sys.run_now(world);
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("thread_local", name = &*sys.name).entered();
+
+            hooks.system(sys, None, |sys| hooks.run_now(&mut **sys, world, false));
         }
+
+        inner.errors.panics.extend(panics.into_inner().unwrap());
+        for sink in &inner.instruments.error_sinks {
+            error::drain_sink(sink, world, &mut inner.errors);
+        }
+        error::propagate(inner.instruments.panic_policy, inner.errors.panics());
     }
 
     /// Waits for all the asynchronously dispatched systems to finish
@@ -94,7 +143,15 @@ where
     ///
     /// See `wait` for executing thread local systems.
     pub fn wait_without_tl(&mut self) {
-        self.data.inner();
+        let inner = self.data.inner();
+        error::propagate(inner.instruments.panic_policy, inner.errors.panics());
+    }
+
+    /// Returns the failures of the last dispatch.
+    ///
+    /// This will wait for the asynchronous systems to finish.
+    pub fn errors(&mut self) -> &DispatchErrors {
+        &self.data.inner().errors
     }
 
     /// Checks if any of the asynchronously dispatched systems are running.
@@ -197,6 +254,8 @@ impl<R> Data<R> {
 }
 
 struct Inner<R> {
+    errors: DispatchErrors,
+    instruments: Instruments,
     stages: Vec<Stage<'static>>,
     world: R,
 }
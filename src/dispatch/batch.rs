@@ -1,6 +1,15 @@
+use std::{
+    cell::Cell,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use smallvec::SmallVec;
+
 use crate::{
     dispatch::Dispatcher, world::ResourceId, Accessor, AccessorCow, DynamicSystemData, RunningTime,
-    System, SystemData, World,
+    System, SystemData, World, Write,
 };
 
 /// The `BatchAccessor` is used to notify the main dispatcher of the read and
@@ -47,6 +56,122 @@ impl<'a> DynamicSystemData<'a> for BatchUncheckedWorld<'a> {
     }
 }
 
+/// The view of the `World` a [`BatchController`] gets, which only allows
+/// fetching the resources declared in its
+/// [`BatchSystemData`][BatchController::BatchSystemData] and dispatching the
+/// sub-dispatcher.
+pub struct BatchWorld<'w, 'a, 'b, 'c> {
+    dispatcher: &'w mut Dispatcher<'a, 'b>,
+    /// The number of `BatchFetch`es the controller still holds.
+    held: &'w Cell<usize>,
+    reads: &'w [ResourceId],
+    world: &'c World,
+    writes: &'w [ResourceId],
+}
+
+impl<'w, 'a, 'b, 'c> BatchWorld<'w, 'a, 'b, 'c> {
+    /// Fetches `T`, e.g. to examine the
+    /// [`BatchSystemData`][BatchController::BatchSystemData] again after a
+    /// dispatch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` accesses resources not declared in the `BatchSystemData`
+    /// of the controller, or writes to resources only declared as read.
+    pub fn fetch<T>(&self) -> BatchFetch<'w, T>
+    where
+        T: SystemData<'c>,
+    {
+        let mut reads = SmallVec::<[ResourceId; 8]>::new();
+        let mut writes = SmallVec::<[ResourceId; 8]>::new();
+        T::extend_reads(&mut reads);
+        T::extend_writes(&mut writes);
+        let held = Some(self.held).filter(|_| !reads.is_empty() || !writes.is_empty());
+
+        let undeclared = reads
+            .into_iter()
+            .find(|id| !self.reads.contains(id) && !self.writes.contains(id))
+            .or_else(|| writes.into_iter().find(|id| !self.writes.contains(id)));
+        if let Some(id) = undeclared {
+            panic!(
+                "Batch controller accessed a resource missing from its `BatchSystemData` ({:?})",
+                id
+            );
+        }
+
+        BatchFetch::new(<T as SystemData>::fetch(self.world), held)
+    }
+
+    /// Dispatches the sub-dispatcher.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the controller still holds a [`BatchFetch`].
+    pub fn dispatch(&mut self) {
+        if self.held.get() != 0 {
+            panic!("Batch controller has to drop its `BatchSystemData` before dispatching");
+        }
+
+        self.dispatcher.dispatch(self.world);
+    }
+}
+
+/// Data fetched for a [`BatchController`], which has to be dropped before
+/// [`BatchWorld::dispatch`] is called.
+///
+/// Data which doesn't borrow any resource, like `()`, may be kept.
+pub struct BatchFetch<'w, T> {
+    data: T,
+    /// Released when the data is dropped or consumed.
+    _held: Held<'w>,
+}
+
+impl<'w, T> BatchFetch<'w, T> {
+    fn new(data: T, held: Option<&'w Cell<usize>>) -> Self {
+        if let Some(held) = held {
+            held.set(held.get() + 1);
+        }
+
+        BatchFetch {
+            data,
+            _held: Held(held),
+        }
+    }
+
+    /// Stops tracking the data, for controllers which consume it before
+    /// dispatching.
+    fn into_inner(self) -> T {
+        self.data
+    }
+}
+
+impl<T> Deref for BatchFetch<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T> DerefMut for BatchFetch<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
+
+/// Counts a `BatchFetch` as held until it's dropped or consumed.
+///
+/// `None` if the data doesn't borrow any resource.
+struct Held<'w>(Option<&'w Cell<usize>>);
+
+impl Drop for Held<'_> {
+    fn drop(&mut self) {
+        if let Some(held) = self.0 {
+            held.set(held.get() - 1);
+        }
+    }
+}
+
 /// The `BatchController` describes things that allow one to control how batches
 /// of systems are executed.
 ///
@@ -60,43 +185,58 @@ impl<'a> DynamicSystemData<'a> for BatchUncheckedWorld<'a> {
 /// [batch_dispatching](https://github.com/amethyst/shred/blob/master/examples/batch_dispatching.rs)
 /// example.
 ///
-/// The [`MultiDispatcher`] may help with implementing this in most common
-/// cases.
+/// The [`MultiDispatcher`] and [`Converge`] may help with implementing this
+/// in most common cases.
 pub trait BatchController<'a, 'b, 'c> {
     /// This associated type has to contain all resources batch controller uses
     /// directly.
     ///
-    /// Note that these are not fetched automatically for the controller, as is
-    /// the case with ordinary [`System`]s. This is because the fetched
-    /// references might need to be dropped before actually dispatching the
-    /// other systems to avoid collisions on them and it would not
-    /// be possible to perform using a parameter.
-    ///
-    /// Therefore, these are only *declared* here, but not automatically
-    /// fetched. If the declaration does not match reality, the scheduler
-    /// might make suboptimal decisions (if this declares more than is
-    /// actually needed) or it may panic in runtime (in case it declares less
-    /// and there happens to be a collision).
+    /// It is fetched for the controller and passed to `run`; the controller
+    /// can't access any other resource.
     type BatchSystemData: SystemData<'c>;
 
     /// The body of the controller.
     ///
-    /// It is allowed to fetch (manually) and examine its
-    /// [`BatchSystemData`][BatchController::BatchSystemData]. Then it shall
-    /// drop all fetched references and is free to call
-    /// `dispatcher.dispatch(world)` as many time as it sees fit.
-    fn run(&mut self, world: &'c World, dispatcher: &mut Dispatcher<'a, 'b>);
+    /// It may examine its `data`. Then it shall drop it and is free to call
+    /// `world.dispatch()` as many times as it sees fit, fetching the data
+    /// again using `world.fetch()` in between if needed.
+    fn run(
+        &mut self,
+        data: BatchFetch<'_, Self::BatchSystemData>,
+        world: BatchWorld<'_, 'a, 'b, 'c>,
+    );
 
     /// Estimate how heavy the whole controller, including the sub-systems, is
     /// in terms of computation costs.
     fn running_time(&self) -> RunningTime {
         RunningTime::VeryLong
     }
+
+    /// Sets up the `World` for the controller itself.
+    ///
+    /// Called after the [`BatchSystemData`][BatchController::BatchSystemData]
+    /// has been set up and before the sub-dispatcher is set up.
+    fn setup(&mut self, world: &mut World) {
+        let _ = world;
+    }
+
+    /// Releases the resources of the controller.
+    ///
+    /// Called after the systems of the sub-dispatcher have been disposed.
+    fn dispose(self, world: &mut World)
+    where
+        Self: Sized,
+    {
+        let _ = world;
+    }
 }
 
 pub(crate) struct BatchControllerSystem<'a, 'b, C> {
     accessor: BatchAccessor,
     controller: C,
+    /// The resources declared by the `BatchSystemData` of the controller.
+    controller_reads: Vec<ResourceId>,
+    controller_writes: Vec<ResourceId>,
     dispatcher: Dispatcher<'a, 'b>,
 }
 
@@ -112,6 +252,8 @@ where
         Self {
             accessor,
             controller,
+            controller_reads: C::BatchSystemData::reads(),
+            controller_writes: C::BatchSystemData::writes(),
             dispatcher,
         }
     }
@@ -124,7 +266,19 @@ where
     type SystemData = BatchUncheckedWorld<'c>;
 
     fn run(&mut self, data: Self::SystemData) {
-        self.controller.run(data.0, &mut self.dispatcher);
+        let held = Cell::new(0);
+        let controller_data = <C::BatchSystemData as SystemData>::fetch(data.0);
+        let accesses = !self.controller_reads.is_empty() || !self.controller_writes.is_empty();
+        let controller_data = BatchFetch::new(controller_data, Some(&held).filter(|_| accesses));
+        let world = BatchWorld {
+            dispatcher: &mut self.dispatcher,
+            held: &held,
+            reads: &self.controller_reads,
+            world: data.0,
+            writes: &self.controller_writes,
+        };
+
+        self.controller.run(controller_data, world);
     }
 
     fn running_time(&self) -> RunningTime {
@@ -137,8 +291,14 @@ where
 
     fn setup(&mut self, world: &mut World) {
         world.setup::<C::BatchSystemData>();
+        self.controller.setup(world);
         self.dispatcher.setup(world);
     }
+
+    fn dispose(self, world: &mut World) {
+        self.dispatcher.dispose(world);
+        self.controller.dispose(world);
+    }
 }
 
 unsafe impl<C: Send> Send for BatchControllerSystem<'_, '_, C> {}
@@ -201,14 +361,170 @@ where
 {
     type BatchSystemData = C::SystemData;
 
-    fn run(&mut self, world: &'c World, dispatcher: &mut Dispatcher<'a, 'b>) {
-        let n = {
-            let plan_data = world.system_data();
-            self.controller.plan(plan_data)
-        };
+    fn run(
+        &mut self,
+        data: BatchFetch<'_, Self::BatchSystemData>,
+        mut world: BatchWorld<'_, 'a, 'b, 'c>,
+    ) {
+        let n = self.controller.plan(data.into_inner());
 
         for _ in 0..n {
-            dispatcher.dispatch(world);
+            world.dispatch();
+        }
+    }
+}
+
+/// The controlling parts of [`BatchController`]s which dispatch a batch until
+/// a condition holds.
+///
+/// A common example is an iterative constraint solver, which runs until the
+/// remaining error is small enough.
+///
+/// To be useful, pass the controller to the constructor of [`Converge`] and
+/// register with [`add_batch`][crate::DispatcherBuilder::add_batch].
+pub trait ConvergeController<'a>: Send {
+    /// What data it needs to decide whether the batch converged.
+    type SystemData: SystemData<'a>;
+
+    /// Decides whether the batch converged, after each dispatch of the
+    /// batch.
+    fn converged(&mut self, data: Self::SystemData) -> bool;
+}
+
+/// A bridge from [`ConvergeController`] to [`BatchController`].
+///
+/// Dispatches the batch until the controller reports convergence, at most
+/// `max_iterations` times, and stores the outcome in the [`Convergence`]
+/// resource.
+///
+/// ## Examples
+///
+/// ```
+/// use shred::{
+///     Converge, ConvergeController, Convergence, DispatcherBuilder, Read, System, World, Write,
+/// };
+///
+/// struct Error(f32);
+///
+/// impl Default for Error {
+///     fn default() -> Self {
+///         Error(1.0)
+///     }
+/// }
+///
+/// struct Solve;
+///
+/// impl<'a> System<'a> for Solve {
+///     type SystemData = Write<'a, Error>;
+///
+///     fn run(&mut self, mut error: Self::SystemData) {
+///         error.0 /= 2.0;
+///     }
+/// }
+///
+/// struct Threshold;
+///
+/// impl<'a> ConvergeController<'a> for Threshold {
+///     type SystemData = Read<'a, Error>;
+///
+///     fn converged(&mut self, error: Self::SystemData) -> bool {
+///         error.0 < 0.1
+///     }
+/// }
+///
+/// let mut dispatcher = DispatcherBuilder::new()
+///     .with_batch(
+///         Converge::new(Threshold, 10),
+///         DispatcherBuilder::new().with(Solve, "solve", &[]),
+///         "solver",
+///         &[],
+///     )
+///     .build();
+///
+/// let mut world = World::empty();
+/// dispatcher.setup(&mut world);
+/// dispatcher.dispatch(&world);
+///
+/// let convergence = world.fetch::<Convergence<Threshold>>();
+/// assert!(convergence.converged);
+/// assert_eq!(convergence.iterations, 4);
+/// ```
+pub struct Converge<C> {
+    controller: C,
+    max_iterations: usize,
+}
+
+impl<C> Converge<C> {
+    /// Constructor.
+    ///
+    /// The `controller` should implement [`ConvergeController`]; the batch is
+    /// dispatched at most `max_iterations` times per run.
+    pub fn new(controller: C, max_iterations: usize) -> Self {
+        Self {
+            controller,
+            max_iterations,
+        }
+    }
+}
+
+impl<'a, 'b, 'c, C> BatchController<'a, 'b, 'c> for Converge<C>
+where
+    C: ConvergeController<'c> + 'static,
+{
+    type BatchSystemData = (C::SystemData, Write<'c, Convergence<C>>);
+
+    fn run(
+        &mut self,
+        data: BatchFetch<'_, Self::BatchSystemData>,
+        mut world: BatchWorld<'_, 'a, 'b, 'c>,
+    ) {
+        drop(data);
+
+        let mut convergence = Convergence::default();
+        while !convergence.converged && convergence.iterations < self.max_iterations {
+            world.dispatch();
+            convergence.iterations += 1;
+            let data = world.fetch::<C::SystemData>().into_inner();
+            convergence.converged = self.controller.converged(data);
+        }
+
+        **world.fetch::<Write<'c, Convergence<C>>>() = convergence;
+    }
+}
+
+/// The outcome of the last run of a [`Converge`] batch controlled by `C`.
+pub struct Convergence<C> {
+    /// How many times the batch was dispatched.
+    pub iterations: usize,
+    /// Whether the controller reported convergence, as opposed to the batch
+    /// hitting its iteration cap.
+    pub converged: bool,
+    marker: PhantomData<fn() -> C>,
+}
+
+impl<C> Clone for Convergence<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for Convergence<C> {}
+
+impl<C> fmt::Debug for Convergence<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Convergence")
+            .field("iterations", &self.iterations)
+            .field("converged", &self.converged)
+            .finish()
+    }
+}
+
+impl<C> Default for Convergence<C> {
+    fn default() -> Self {
+        Convergence {
+            iterations: 0,
+            converged: false,
+            marker: PhantomData,
         }
     }
 }
@@ -216,7 +532,10 @@ where
 #[cfg(test)]
 mod tests {
 
-    use crate::{BatchController, Dispatcher, DispatcherBuilder, System, World, Write};
+    use super::{BatchFetch, BatchWorld, Converge, ConvergeController, Convergence};
+    use crate::{
+        BatchController, DispatcherBuilder, PanicPolicy, Read, System, TrySystem, World, Write,
+    };
 
     /// This test demonstrate that the batch system is able to correctly setup
     /// its resources to default datas.
@@ -368,6 +687,152 @@ mod tests {
         }
     }
 
+    /// The systems and the controller of a batch are disposed together with
+    /// the parent dispatcher.
+    #[test]
+    fn test_dispose() {
+        let mut dispatcher = DispatcherBuilder::new()
+            .with_batch(
+                AuditController,
+                DispatcherBuilder::new().with(StockTakingSystem, "stock_taking_system", &[]),
+                "BatchSystemTest",
+                &[],
+            )
+            .build();
+
+        let mut world = World::empty();
+        dispatcher.setup(&mut world);
+        assert_eq!(world.fetch::<CustomerWallet>().cents_count, 0);
+
+        dispatcher.dispose(&mut world);
+        assert_eq!(world.fetch::<PotatoStore>().potato_count, 0);
+        assert!(!world.has_value::<CustomerWallet>());
+    }
+
+    /// A controller can't fetch resources it didn't declare.
+    #[test]
+    #[should_panic(expected = "Batch controller accessed a resource missing from its")]
+    fn test_undeclared_fetch() {
+        let mut dispatcher = DispatcherBuilder::new()
+            .with_batch(
+                SneakyController,
+                DispatcherBuilder::new().with(BuyPotatoSystem, "buy_potato_system", &[]),
+                "BatchSystemTest",
+                &[],
+            )
+            .build();
+
+        let mut world = World::empty();
+        dispatcher.setup(&mut world);
+        dispatcher.dispatch_seq(&world);
+    }
+
+    /// A controller has to drop its data before dispatching.
+    #[test]
+    #[should_panic(expected = "Batch controller has to drop its `BatchSystemData`")]
+    fn test_dispatch_holding_data() {
+        let mut dispatcher = DispatcherBuilder::new()
+            .with_batch(
+                HoldingController,
+                DispatcherBuilder::new().with(BuyPotatoSystem, "buy_potato_system", &[]),
+                "BatchSystemTest",
+                &[],
+            )
+            .build();
+
+        let mut world = World::empty();
+        dispatcher.setup(&mut world);
+        dispatcher.dispatch_seq(&world);
+    }
+
+    /// Data fetched again has to be dropped before dispatching as well.
+    #[test]
+    #[should_panic(expected = "Batch controller has to drop its `BatchSystemData`")]
+    fn test_dispatch_holding_fetch() {
+        let mut dispatcher = DispatcherBuilder::new()
+            .with_batch(
+                RefetchingController,
+                DispatcherBuilder::new().with(BuyPotatoSystem, "buy_potato_system", &[]),
+                "BatchSystemTest",
+                &[],
+            )
+            .build();
+
+        let mut world = World::empty();
+        dispatcher.setup(&mut world);
+        dispatcher.dispatch_seq(&world);
+    }
+
+    /// The errors of `TrySystem`s in a batch are collected by the parent
+    /// dispatcher.
+    #[test]
+    fn test_batch_try_system_errors() {
+        let mut dispatcher = DispatcherBuilder::new()
+            .with_batch(
+                CustomBatchControllerSystem,
+                DispatcherBuilder::new().with_try(SoldOutSystem, "sold_out_system", &[]),
+                "BatchSystemTest",
+                &[],
+            )
+            .build();
+
+        let mut world = World::empty();
+        dispatcher.setup(&mut world);
+        dispatcher.dispatch(&world);
+
+        let errors = dispatcher.errors().errors();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].name(), "sold_out_system");
+    }
+
+    /// The panics caught by a batch are reported by the parent dispatcher.
+    #[test]
+    fn test_batch_panics() {
+        let mut dispatcher = DispatcherBuilder::new()
+            .with_batch(
+                CustomBatchControllerSystem,
+                DispatcherBuilder::new()
+                    .with(BankruptSystem, "bankrupt_system", &[])
+                    .with_panic_policy(PanicPolicy::Continue),
+                "BatchSystemTest",
+                &[],
+            )
+            .build();
+
+        let mut world = World::empty();
+        dispatcher.setup(&mut world);
+        dispatcher.dispatch(&world);
+
+        let panics = dispatcher.errors().panics();
+        assert_eq!(panics.len(), 3);
+        assert_eq!(panics[0].name(), "bankrupt_system");
+        assert_eq!(panics[0].message(), Some("Bankrupt"));
+    }
+
+    /// A `Converge` batch stops at its iteration cap if the controller never
+    /// reports convergence.
+    #[test]
+    fn test_converge_cap() {
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(OpenStoresSystem, "open_stores_system", &[])
+            .with_batch(
+                Converge::new(NeverSoldOut, 5),
+                DispatcherBuilder::new().with(BuyPotatoSystem, "buy_potato_system", &[]),
+                "BatchSystemTest",
+                &["open_stores_system"],
+            )
+            .build();
+
+        let mut world = World::empty();
+        dispatcher.setup(&mut world);
+        dispatcher.dispatch(&world);
+
+        let convergence = *world.fetch::<Convergence<NeverSoldOut>>();
+        assert_eq!(convergence.iterations, 5);
+        assert!(!convergence.converged);
+        assert_eq!(world.fetch::<PotatoStore>().potato_count, 45);
+    }
+
     // Resources
 
     #[derive(Debug, Clone, Copy)]
@@ -435,6 +900,17 @@ mod tests {
         }
     }
 
+    pub struct SoldOutSystem;
+
+    impl<'a> TrySystem<'a> for SoldOutSystem {
+        type Error = String;
+        type SystemData = Read<'a, PotatoStore>;
+
+        fn run(&mut self, _: Self::SystemData) -> Result<(), String> {
+            Err("Sold out".to_owned())
+        }
+    }
+
     // Buy Systems
 
     pub struct BuyPotatoSystem;
@@ -487,15 +963,120 @@ mod tests {
 
     // Custom Batch Controller which dispatch the systems three times
 
+    pub struct StockTakingSystem;
+
+    impl<'a> System<'a> for StockTakingSystem {
+        type SystemData = Read<'a, PotatoStore>;
+
+        fn run(&mut self, _: Self::SystemData) {}
+
+        fn dispose(self, world: &mut World) {
+            world.fetch_mut::<PotatoStore>().potato_count = 0;
+        }
+    }
+
     pub struct CustomBatchControllerSystem;
 
-    impl<'a, 'b> BatchController<'a, 'b, '_> for CustomBatchControllerSystem {
+    impl<'a, 'b, 'c> BatchController<'a, 'b, 'c> for CustomBatchControllerSystem {
         type BatchSystemData = ();
 
-        fn run(&mut self, world: &World, dispatcher: &mut Dispatcher<'a, 'b>) {
+        fn run(
+            &mut self,
+            _: BatchFetch<'_, Self::BatchSystemData>,
+            mut world: BatchWorld<'_, 'a, 'b, 'c>,
+        ) {
             for _i in 0..3 {
-                dispatcher.dispatch(world);
+                world.dispatch();
             }
         }
     }
+
+    pub struct BankruptSystem;
+
+    impl<'a> System<'a> for BankruptSystem {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {
+            panic!("Bankrupt");
+        }
+    }
+
+    pub struct AuditController;
+
+    impl<'a, 'b, 'c> BatchController<'a, 'b, 'c> for AuditController {
+        type BatchSystemData = ();
+
+        fn run(
+            &mut self,
+            _: BatchFetch<'_, Self::BatchSystemData>,
+            mut world: BatchWorld<'_, 'a, 'b, 'c>,
+        ) {
+            world.dispatch();
+        }
+
+        fn setup(&mut self, world: &mut World) {
+            world.insert(CustomerWallet { cents_count: 0 });
+        }
+
+        fn dispose(self, world: &mut World) {
+            world.remove::<CustomerWallet>();
+        }
+    }
+
+    pub struct SneakyController;
+
+    impl<'a, 'b, 'c> BatchController<'a, 'b, 'c> for SneakyController {
+        type BatchSystemData = Read<'c, PotatoStore>;
+
+        fn run(
+            &mut self,
+            _: BatchFetch<'_, Self::BatchSystemData>,
+            world: BatchWorld<'_, 'a, 'b, 'c>,
+        ) {
+            world.fetch::<Write<'c, CustomerWallet>>();
+        }
+    }
+
+    pub struct HoldingController;
+
+    impl<'a, 'b, 'c> BatchController<'a, 'b, 'c> for HoldingController {
+        type BatchSystemData = Read<'c, PotatoStore>;
+
+        fn run(
+            &mut self,
+            data: BatchFetch<'_, Self::BatchSystemData>,
+            mut world: BatchWorld<'_, 'a, 'b, 'c>,
+        ) {
+            world.dispatch();
+            drop(data);
+        }
+    }
+
+    pub struct RefetchingController;
+
+    impl<'a, 'b, 'c> BatchController<'a, 'b, 'c> for RefetchingController {
+        type BatchSystemData = Read<'c, PotatoStore>;
+
+        fn run(
+            &mut self,
+            data: BatchFetch<'_, Self::BatchSystemData>,
+            mut world: BatchWorld<'_, 'a, 'b, 'c>,
+        ) {
+            drop(data);
+
+            let store = world.fetch::<Read<'c, PotatoStore>>();
+            world.dispatch();
+            drop(store);
+        }
+    }
+
+    pub struct NeverSoldOut;
+
+    impl<'a> ConvergeController<'a> for NeverSoldOut {
+        type SystemData = Read<'a, PotatoStore>;
+
+        fn converged(&mut self, store: Self::SystemData) -> bool {
+            store.potato_count < 0
+        }
+    }
 }
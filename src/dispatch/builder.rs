@@ -1,23 +1,42 @@
-use std::{collections::hash_map::Entry, fmt};
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
 use ahash::AHashMap as HashMap;
 
 #[cfg(feature = "parallel")]
-use crate::dispatch::dispatcher::ThreadPoolWrapper;
+use crate::dispatch::{dispatcher::ThreadPoolWrapper, util::SharedSlot};
 use crate::{
     dispatch::{
         BatchAccessor, BatchController, Dispatcher,
         batch::BatchControllerSystem,
-        dispatcher::{SystemId, ThreadLocal},
-        stage::StagesBuilder,
+        dispatcher::{NamedSystem, SystemExecSend, SystemId, ThreadLocal},
+        error::{BuildError, ErrorSink, PanicPolicy, TryRunner},
+        graph::{SystemGraph, system_name},
+        observer::{DispatchObserver, Instruments, Observers},
+        partition::PartitionSystem,
+        stage::{Stage, SystemAccess},
+        trace::{TraceRecorder, TraceRecorderWrapper, Tracer},
+        util::Accessible,
     },
-    system::{RunNow, System, SystemData},
+    system::{RunNow, System, SystemData, TrySystem},
+    world::ResourceId,
 };
 
 /// Builder for the [`Dispatcher`].
 ///
 /// [`Dispatcher`]: struct.Dispatcher.html
 ///
+/// ## Dependencies
+///
+/// Dependencies and ordering constraints are only resolved by `build()` /
+/// `try_build()`, so systems may depend on systems which are added later.
+/// A system can be ordered before another one using `add_order()`.
+///
 /// ## Barriers
 ///
 /// Barriers are a way of sequentializing parts of
@@ -94,12 +113,22 @@ use crate::{
 /// ```
 #[derive(Default)]
 pub struct DispatcherBuilder<'a, 'b> {
-    current_id: usize,
-    map: HashMap<String, SystemId>,
-    pub(crate) stages_builder: StagesBuilder<'a>,
+    /// The first sink is used by new `TrySystem`s, the others belong to
+    /// batches and merged builders.
+    error_sinks: Vec<ErrorSink>,
+    graph: SystemGraph,
+    observers: Observers,
+    panic_policy: Option<PanicPolicy>,
+    /// The systems of the graph, placed into stages by `build`.
+    systems: HashMap<SystemId, SystemExecSend<'a>>,
     thread_local: ThreadLocal<'b>,
     #[cfg(feature = "parallel")]
-    thread_pool: ::std::sync::Arc<::std::sync::RwLock<ThreadPoolWrapper>>,
+    thread_pool: SharedSlot<ThreadPoolWrapper>,
+    trace_recorder: TraceRecorderWrapper,
+    /// The ids reported by the `TrySystem`s, shifted when this builder is
+    /// merged into another one.
+    try_ids: Vec<Arc<AtomicUsize>>,
+    unchecked_fetch: bool,
 }
 
 impl<'a, 'b> DispatcherBuilder<'a, 'b> {
@@ -114,25 +143,25 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
 
     /// Returns whether or not any system has been added to the builder
     pub fn is_empty(&self) -> bool {
-        self.map.is_empty()
+        self.graph.map.is_empty()
     }
 
     /// Returns the number of systems added to the builder
     pub fn num_systems(&self) -> usize {
-        self.map.len()
+        self.graph.map.len()
     }
 
     /// Returns whether or not a specific system has been added to the builder
-    /// This is useful as [`add()`](struct.DispatcherBuilder.html#method.add)
+    /// This is useful as [`build()`](struct.DispatcherBuilder.html#method.build)
     /// will throw if a dependency does not exist So you can use this
     /// function to check if dependencies are satisfied
     pub fn has_system(&self, system: &str) -> bool {
-        self.map.contains_key(system)
+        self.graph.map.contains_key(system)
     }
 
     /// Adds a new system with a given name and a list of dependencies.
-    /// Please note that the dependency should be added before
-    /// you add the depending system.
+    /// The dependencies don't need to be added yet, they are resolved by
+    /// `build()`.
     ///
     /// If you want to register systems which can not be specified as
     /// dependencies, you can use `""` as their name, which will not panic
@@ -143,7 +172,6 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     ///
     /// # Panics
     ///
-    /// * if the specified dependency does not exist
     /// * if a system with the same name was already registered.
     pub fn with<T>(mut self, system: T, name: &str, dep: &[&str]) -> Self
     where
@@ -155,8 +183,8 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     }
 
     /// Adds a new system with a given name and a list of dependencies.
-    /// Please note that the dependency should be added before
-    /// you add the depending system.
+    /// The dependencies don't need to be added yet, they are resolved by
+    /// `build()`.
     ///
     /// If you want to register systems which can not be specified as
     /// dependencies, you can use `""` as their name, which will not panic
@@ -164,42 +192,320 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     ///
     /// # Panics
     ///
-    /// * if the specified dependency does not exist
     /// * if a system with the same name was already registered.
     pub fn add<T>(&mut self, system: T, name: &str, dep: &[&str])
     where
         T: for<'c> System<'c> + Send + 'a,
     {
-        let id = self.next_id();
-
-        let dependencies = dep
-            .iter()
-            .map(|x| {
-                *self
-                    .map
-                    .get(*x)
-                    .unwrap_or_else(|| panic!("No such system registered (\"{}\")", *x))
-            })
-            .collect();
-
-        if !name.is_empty() {
-            if let Entry::Vacant(e) = self.map.entry(name.to_owned()) {
-                e.insert(id);
-            } else {
-                panic!(
-                    "Cannot insert multiple systems with the same name (\"{}\")",
-                    name
-                );
-            }
+        let id = self.graph.next_id();
+        let display_name = system_name::<T>(name);
+
+        self.insert(id, system, name, display_name, &[], dep);
+    }
+
+    /// Adds a new `'static` system, which can be accessed from the built
+    /// dispatcher with [`Dispatcher::system`] without implementing
+    /// [`System::as_any`].
+    ///
+    /// Same as
+    /// [`add_accessible()`](struct.DispatcherBuilder.html#method.add_accessible),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// * if a system with the same name was already registered.
+    pub fn with_accessible<T>(mut self, system: T, name: &str, dep: &[&str]) -> Self
+    where
+        T: for<'c> System<'c> + Send + 'static,
+    {
+        self.add_accessible(system, name, dep);
+
+        self
+    }
+
+    /// Adds a new `'static` system, which can be accessed from the built
+    /// dispatcher.
+    ///
+    /// See
+    /// [`with_accessible()`](struct.DispatcherBuilder.html#method.with_accessible)
+    /// for details.
+    ///
+    /// # Panics
+    ///
+    /// * if a system with the same name was already registered.
+    pub fn add_accessible<T>(&mut self, system: T, name: &str, dep: &[&str])
+    where
+        T: for<'c> System<'c> + Send + 'static,
+    {
+        let id = self.graph.next_id();
+        let display_name = system_name::<T>(name);
+
+        self.insert(id, Accessible(system), name, display_name, &[], dep);
+    }
+
+    /// Adds a new system with a given name, a list of labels and a list of
+    /// dependencies.
+    ///
+    /// Labels group systems without knowing their names: a dependency may
+    /// name a label instead of a system, which means "after every system with
+    /// that label". See
+    /// [`order_sets()`](struct.DispatcherBuilder.html#method.order_sets) for
+    /// ordering whole groups.
+    ///
+    /// Same as
+    /// [`add_labeled()`](struct.DispatcherBuilder.html#method.add_labeled),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// * if a system with the same name was already registered.
+    pub fn with_labeled<T>(
+        mut self,
+        system: T,
+        name: &str,
+        labels: &[&str],
+        dep: &[&str],
+    ) -> Self
+    where
+        T: for<'c> System<'c> + Send + 'a,
+    {
+        self.add_labeled(system, name, labels, dep);
+
+        self
+    }
+
+    /// Adds a new system with a given name, a list of labels and a list of
+    /// dependencies.
+    ///
+    /// See
+    /// [`with_labeled()`](struct.DispatcherBuilder.html#method.with_labeled)
+    /// for details.
+    ///
+    /// # Panics
+    ///
+    /// * if a system with the same name was already registered.
+    pub fn add_labeled<T>(&mut self, system: T, name: &str, labels: &[&str], dep: &[&str])
+    where
+        T: for<'c> System<'c> + Send + 'a,
+    {
+        let id = self.graph.next_id();
+        let display_name = system_name::<T>(name);
+
+        self.insert(id, system, name, display_name, labels, dep);
+    }
+
+    /// Makes all systems labeled `after` run after all systems labeled
+    /// `before`, no matter in which order they are added.
+    ///
+    /// This is the same as
+    /// [`add_order()`](struct.DispatcherBuilder.html#method.add_order).
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// # use shred::{DispatcherBuilder, System};
+    /// # struct Sys;
+    /// # impl<'a> System<'a> for Sys {
+    /// #     type SystemData = ();
+    /// #     fn run(&mut self, _: ()) {}
+    /// # }
+    /// let dispatcher = DispatcherBuilder::new()
+    ///     // runs after "keyboard" and "gamepad"
+    ///     .with_labeled(Sys, "collision", &["physics"], &[])
+    ///     .with_labeled(Sys, "keyboard", &["input"], &[])
+    ///     .with_labeled(Sys, "gamepad", &["input"], &[])
+    ///     .with_set_order("input", "physics")
+    ///     .build();
+    /// ```
+    pub fn order_sets(&mut self, before: &str, after: &str) {
+        self.add_order(before, after);
+    }
+
+    /// Same as
+    /// [`order_sets()`](struct.DispatcherBuilder.html#method.order_sets), but
+    /// returns `self` to enable method chaining.
+    pub fn with_set_order(mut self, before: &str, after: &str) -> Self {
+        self.order_sets(before, after);
+
+        self
+    }
+
+    /// Makes `before` run before `after`. Both may name a system or a label.
+    ///
+    /// Unlike dependencies, constraints naming neither a system nor a label
+    /// are ignored, so optional systems can be ordered against.
+    ///
+    /// Same as [`add_order()`](struct.DispatcherBuilder.html#method.add_order),
+    /// but returns `self` to enable method chaining.
+    pub fn with_order(mut self, before: &str, after: &str) -> Self {
+        self.add_order(before, after);
+
+        self
+    }
+
+    /// Makes `before` run before `after`. Both may name a system or a label.
+    ///
+    /// See [`with_order()`](struct.DispatcherBuilder.html#method.with_order)
+    /// for details.
+    pub fn add_order(&mut self, before: &str, after: &str) {
+        self.graph.order.push((before.to_owned(), after.to_owned()));
+    }
+
+    /// Adds a new [`TrySystem`] with a given name and a list of dependencies.
+    ///
+    /// Errors returned by the system end up in [`Dispatcher::errors`], or in
+    /// the [`ErrorHandler`](crate::ErrorHandler) resource if the `World`
+    /// contains one. Errors of systems inside of batches are reported by the
+    /// top level dispatcher.
+    ///
+    /// Same as [`add_try()`](struct.DispatcherBuilder.html#method.add_try),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// * if a system with the same name was already registered.
+    pub fn with_try<T>(mut self, system: T, name: &str, dep: &[&str]) -> Self
+    where
+        T: for<'c> TrySystem<'c> + Send + 'a,
+    {
+        self.add_try(system, name, dep);
+
+        self
+    }
+
+    /// Adds a new [`TrySystem`] with a given name and a list of dependencies.
+    ///
+    /// See [`with_try()`](struct.DispatcherBuilder.html#method.with_try) for
+    /// details.
+    ///
+    /// # Panics
+    ///
+    /// * if a system with the same name was already registered.
+    pub fn add_try<T>(&mut self, system: T, name: &str, dep: &[&str])
+    where
+        T: for<'c> TrySystem<'c> + Send + 'a,
+    {
+        let id = self.graph.next_id();
+        let display_name = system_name::<T>(name);
+        let try_id = Arc::new(AtomicUsize::new(id.0));
+        self.try_ids.push(try_id.clone());
+        let system = TryRunner {
+            id: try_id,
+            name: display_name.clone(),
+            sink: self.error_sink(),
+            system,
+        };
+
+        self.insert(id, system, name, display_name, &[], dep);
+    }
+
+    fn insert<T>(
+        &mut self,
+        id: SystemId,
+        system: T,
+        name: &str,
+        display_name: Arc<str>,
+        labels: &[&str],
+        dep: &[&str],
+    ) where
+        T: for<'c> System<'c> + Send + 'a,
+    {
+        let access = SystemAccess::of(&system);
+        if let Err(e) = self.graph.add(id, name, display_name, access, labels, dep) {
+            panic!("{}", e);
         }
 
-        self.stages_builder.insert(dependencies, id, system);
+        self.systems.insert(id, Box::new(system));
     }
 
     /// Returns `true` if a system with the given name has been added to the
     /// `BispatcherBuilder`, otherwise, returns false.
     pub fn contains(&self, name: &str) -> bool {
-        self.map.contains_key(name)
+        self.graph.map.contains_key(name)
+    }
+
+    /// Removes the system registered as `name`, returning `false` if there is
+    /// no such system.
+    ///
+    /// Systems depending on the removed system make `build()` fail, unless
+    /// another system is added under the same name. Ordering constraints
+    /// naming it are ignored.
+    pub fn remove(&mut self, name: &str) -> bool {
+        match self.graph.remove(name) {
+            Some(id) => {
+                self.systems.remove(&id);
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces the system registered as `name` with `system`, keeping its
+    /// dependencies, labels and position relative to barriers.
+    ///
+    /// This is useful to swap out a single system of an existing pipeline,
+    /// e.g. for a mock in tests.
+    ///
+    /// # Panics
+    ///
+    /// * if there is no system registered as `name`.
+    pub fn replace<T>(&mut self, name: &str, system: T)
+    where
+        T: for<'c> System<'c> + Send + 'a,
+    {
+        let id = match self.graph.map.get(name) {
+            Some(&id) => id,
+            None => panic!("No such system registered (\"{}\")", name),
+        };
+
+        self.graph.node_mut(id).access = SystemAccess::of(&system);
+        self.systems.insert(id, Box::new(system));
+    }
+
+    /// Adds all systems, thread local systems, ordering constraints and
+    /// observers of `other` to this builder.
+    ///
+    /// The systems of `other` keep their names and dependencies and are
+    /// placed as if they were added after the systems of this builder, so
+    /// barriers of `other` only separate its own systems. The panic policy,
+    /// thread pool and trace recorder of this builder take precedence.
+    ///
+    /// Fails without changing this builder if both builders have systems
+    /// with the same name.
+    pub fn merge(&mut self, other: DispatcherBuilder<'a, 'b>) -> Result<(), BuildError> {
+        let offset = self.graph.merge(other.graph)?;
+        let id = |id: SystemId| SystemId(id.0 + offset);
+
+        self.systems.extend(other.systems.into_iter().map(|(old, system)| (id(old), system)));
+        for try_id in &other.try_ids {
+            try_id.fetch_add(offset, Ordering::Relaxed);
+        }
+        self.try_ids.extend(other.try_ids);
+        self.thread_local
+            .extend(other.thread_local.into_iter().map(|system| NamedSystem {
+                id: id(system.id),
+                ..system
+            }));
+
+        self.error_sinks.extend(other.error_sinks);
+        self.observers.extend(other.observers);
+        self.panic_policy = self.panic_policy.or(other.panic_policy);
+
+        // batches of `other` keep using its slots, which now share ours
+        #[cfg(feature = "parallel")]
+        {
+            let pool = other.thread_pool.read(Clone::clone);
+            self.thread_pool.write(|own| *own = own.take().or(pool));
+            other.thread_pool.link(&self.thread_pool);
+        }
+
+        let recorder = other.trace_recorder.read(|state| state.recorder.clone());
+        self.trace_recorder
+            .write(|state| state.recorder = state.recorder.take().or(recorder));
+        other.trace_recorder.link(&self.trace_recorder);
+
+        Ok(())
     }
 
     /// The `Batch` is a `System` which contains a `Dispatcher`.
@@ -254,40 +560,149 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// This mean that the dependencies, the `System` names, etc.. specified on
     /// the `Batch` `Dispatcher` are not visible on the parent, and is not
     /// allowed to specify cross dependencies.
+    ///
+    /// # Panics
+    ///
+    /// * if the systems of `dispatcher_builder` can't be resolved, see
+    ///   [`try_build()`](struct.DispatcherBuilder.html#method.try_build)
+    /// * if a system with the same name was already registered.
     pub fn add_batch<T>(
         &mut self,
         controller: T,
-        mut dispatcher_builder: DispatcherBuilder<'a, 'b>,
+        dispatcher_builder: DispatcherBuilder<'a, 'b>,
         name: &str,
         dep: &[&str],
     ) where
         T: for<'c> BatchController<'a, 'b, 'c> + Send + 'a,
         'b: 'a,
     {
-        #[cfg(feature = "parallel")]
-        {
-            dispatcher_builder.thread_pool = self.thread_pool.clone();
-        }
+        let (dispatcher, mut reads, mut writes) =
+            self.build_sub_dispatcher(dispatcher_builder, name);
 
-        let mut reads = dispatcher_builder.stages_builder.fetch_all_reads();
         reads.extend(<T::BatchSystemData as SystemData>::reads());
         reads.sort();
         reads.dedup();
-
-        let mut writes = dispatcher_builder.stages_builder.fetch_all_writes();
         writes.extend(<T::BatchSystemData as SystemData>::writes());
         writes.sort();
         writes.dedup();
 
         let accessor = BatchAccessor::new(reads, writes);
-        let dispatcher: Dispatcher<'a, 'b> = dispatcher_builder.build();
-
         let batch_system =
             unsafe { BatchControllerSystem::<'a, 'b, T>::create(accessor, controller, dispatcher) };
 
         self.add(batch_system, name, dep);
     }
 
+    /// Adds a partitioned batch, which runs a copy of a sub-dispatcher for
+    /// every partition in `partitions`, concurrently if possible.
+    ///
+    /// `build` is called with each partition, and is expected to bind the
+    /// systems it adds to the resources inserted with that dynamic id, by
+    /// writing them against [`PartitionData`](crate::PartitionData) with a
+    /// [`PartitionAccessor`](crate::PartitionAccessor) of the partition. The
+    /// copies can run concurrently as long as they don't conflict, so other
+    /// systems of the copies may only read resources shared by all
+    /// partitions; a system fetching `PartitionData` can't access them.
+    ///
+    /// Same as [DispatcherBuilder::add_partitioned], but returns `self` to
+    /// enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// Same as [DispatcherBuilder::add_partitioned].
+    pub fn with_partitioned<F>(
+        mut self,
+        partitions: &[u64],
+        build: F,
+        name: &str,
+        dep: &[&str],
+    ) -> Self
+    where
+        F: FnMut(u64) -> DispatcherBuilder<'a, 'b>,
+        'b: 'a,
+    {
+        self.add_partitioned(partitions, build, name, dep);
+
+        self
+    }
+
+    /// Adds a partitioned batch, which runs a copy of a sub-dispatcher for
+    /// every partition in `partitions`, concurrently if possible.
+    ///
+    /// `build` is called with each partition, and is expected to bind the
+    /// systems it adds to the resources inserted with that dynamic id, by
+    /// writing them against [`PartitionData`](crate::PartitionData) with a
+    /// [`PartitionAccessor`](crate::PartitionAccessor) of the partition. The
+    /// copies can run concurrently as long as they don't conflict, so other
+    /// systems of the copies may only read resources shared by all
+    /// partitions; a system fetching `PartitionData` can't access them.
+    ///
+    /// # Panics
+    ///
+    /// * if the copies of two partitions conflict, i.e. one of them writes a
+    ///   resource the other one accesses
+    /// * if a sub-dispatcher contains thread local systems
+    /// * if the systems of a sub-dispatcher can't be resolved, see
+    ///   [`try_build()`](struct.DispatcherBuilder.html#method.try_build)
+    /// * if a system with the same name was already registered.
+    pub fn add_partitioned<F>(
+        &mut self,
+        partitions: &[u64],
+        mut build: F,
+        name: &str,
+        dep: &[&str],
+    ) where
+        F: FnMut(u64) -> DispatcherBuilder<'a, 'b>,
+        'b: 'a,
+    {
+        let mut copies: Vec<(u64, Vec<ResourceId>, Vec<ResourceId>)> =
+            Vec::with_capacity(partitions.len());
+        let mut dispatchers = Vec::with_capacity(partitions.len());
+
+        for &partition in partitions {
+            let (dispatcher, reads, writes) = self.build_sub_dispatcher(build(partition), name);
+
+            for (other, other_reads, other_writes) in &copies {
+                let conflict = writes
+                    .iter()
+                    .find(|id| other_reads.contains(id) || other_writes.contains(id))
+                    .or_else(|| reads.iter().find(|id| other_writes.contains(id)));
+                if let Some(id) = conflict {
+                    panic!(
+                        "Partitions {} and {} of \"{}\" conflict on the resource {:?}",
+                        other, partition, name, id
+                    );
+                }
+            }
+
+            let dispatcher = dispatcher.try_into_sendable().unwrap_or_else(|_| {
+                panic!("Partitioned batch \"{}\" can't contain thread local systems", name)
+            });
+            dispatchers.push(dispatcher);
+            copies.push((partition, reads, writes));
+        }
+
+        let mut reads = Vec::new();
+        let mut writes = Vec::new();
+        for (_, copy_reads, copy_writes) in copies {
+            reads.extend(copy_reads);
+            writes.extend(copy_writes);
+        }
+        reads.sort();
+        reads.dedup();
+        writes.sort();
+        writes.dedup();
+
+        let system = PartitionSystem {
+            accessor: BatchAccessor::new(reads, writes),
+            dispatchers,
+            #[cfg(feature = "parallel")]
+            thread_pool: self.thread_pool.clone(),
+        };
+
+        self.add(system, name, dep);
+    }
+
     /// Adds a new thread local system.
     ///
     /// Please only use this if your struct is not `Send` and `Sync`.
@@ -314,7 +729,48 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     where
         T: for<'c> RunNow<'c> + 'b,
     {
-        self.thread_local.push(Box::new(system));
+        let id = self.graph.next_id();
+
+        self.thread_local.push(NamedSystem::new(
+            id,
+            tynm::type_name::<T>().into(),
+            Box::new(system),
+        ));
+    }
+
+    /// Adds a new `'static` thread local system, which can be accessed from
+    /// the built dispatcher with [`Dispatcher::thread_local_system`] without
+    /// implementing [`System::as_any`].
+    ///
+    /// Same as
+    /// [`add_thread_local_accessible()`](struct.DispatcherBuilder.html#method.add_thread_local_accessible),
+    /// but returns `self` to enable method chaining.
+    pub fn with_thread_local_accessible<T>(mut self, system: T) -> Self
+    where
+        T: for<'c> System<'c> + 'static,
+    {
+        self.add_thread_local_accessible(system);
+
+        self
+    }
+
+    /// Adds a new `'static` thread local system, which can be accessed from
+    /// the built dispatcher.
+    ///
+    /// See
+    /// [`with_thread_local_accessible()`](struct.DispatcherBuilder.html#method.with_thread_local_accessible)
+    /// for details.
+    pub fn add_thread_local_accessible<T>(&mut self, system: T)
+    where
+        T: for<'c> System<'c> + 'static,
+    {
+        let id = self.graph.next_id();
+
+        self.thread_local.push(NamedSystem::new(
+            id,
+            tynm::type_name::<T>().into(),
+            Box::new(Accessible(system)),
+        ));
     }
 
     /// Inserts a barrier which assures that all systems
@@ -345,7 +801,7 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// Thread-local systems are not affected by barriers;
     /// they're always executed at the end.
     pub fn add_barrier(&mut self) {
-        self.stages_builder.add_barrier();
+        self.graph.barrier += 1;
     }
 
     /// Attach a rayon thread pool to the builder
@@ -365,7 +821,130 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// and use that instead of creating one.
     #[cfg(feature = "parallel")]
     pub fn add_pool(&mut self, pool: ::std::sync::Arc<::rayon::ThreadPool>) {
-        *self.thread_pool.write().unwrap() = Some(pool);
+        self.thread_pool.write(|own| *own = Some(pool));
+    }
+
+    /// Attaches a [`TraceRecorder`] which records every system run of the
+    /// built dispatcher, including the ones of its batches.
+    ///
+    /// Same as
+    /// [`add_trace_recorder()`](struct.DispatcherBuilder.html#method.add_trace_recorder),
+    /// but returns `self` to enable method chaining.
+    pub fn with_trace_recorder(mut self, recorder: Arc<TraceRecorder>) -> Self {
+        self.add_trace_recorder(recorder);
+
+        self
+    }
+
+    /// Attaches a [`TraceRecorder`] which records every system run of the
+    /// built dispatcher, including the ones of its batches.
+    pub fn add_trace_recorder(&mut self, recorder: Arc<TraceRecorder>) {
+        self.trace_recorder.write(|state| state.recorder = Some(recorder));
+    }
+
+    /// Registers a [`DispatchObserver`] which gets notified about the
+    /// execution of the built dispatcher.
+    ///
+    /// Same as
+    /// [`add_observer()`](struct.DispatcherBuilder.html#method.add_observer),
+    /// but returns `self` to enable method chaining.
+    pub fn with_observer<O>(mut self, observer: O) -> Self
+    where
+        O: DispatchObserver + 'static,
+    {
+        self.add_observer(observer);
+
+        self
+    }
+
+    /// Registers a [`DispatchObserver`] which gets notified about the
+    /// execution of the built dispatcher.
+    ///
+    /// Observers are called in the order they were registered in.
+    pub fn add_observer<O>(&mut self, observer: O)
+    where
+        O: DispatchObserver + 'static,
+    {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Enables panic isolation: every system runs under `catch_unwind`, so a
+    /// panicking system no longer aborts the whole dispatch. The caught panics
+    /// are available from [`Dispatcher::errors`] and `policy` decides what
+    /// happens afterwards.
+    ///
+    /// Batches don't inherit the policy, they need to be configured
+    /// separately.
+    ///
+    /// Same as
+    /// [`set_panic_policy()`](struct.DispatcherBuilder.html#method.set_panic_policy),
+    /// but returns `self` to enable method chaining.
+    pub fn with_panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.set_panic_policy(policy);
+
+        self
+    }
+
+    /// Enables panic isolation with the given `policy`.
+    ///
+    /// See [`with_panic_policy()`](struct.DispatcherBuilder.html#method.with_panic_policy)
+    /// for details.
+    pub fn set_panic_policy(&mut self, policy: PanicPolicy) {
+        self.panic_policy = Some(policy);
+    }
+
+    /// Lets the systems fetch their `SystemData` without the runtime borrow
+    /// checking of the resources, relying on the scheduler instead, which
+    /// never runs conflicting systems at the same time. This saves the atomic
+    /// borrow counting of every fetched resource.
+    ///
+    /// Only the system data the dispatcher fetches before [`System::run`] is
+    /// unchecked; resources fetched while running, e.g. by batch controllers
+    /// or nested calls to [`RunNow::run_now`], are still checked. Builds with
+    /// `debug_assertions` or the `check-borrows` feature ignore this setting
+    /// and check every borrow.
+    ///
+    /// Requires the `unchecked-fetch` feature, without which no fetch pays
+    /// for the support of unchecked borrows.
+    ///
+    /// Batches don't inherit this setting, they need to opt in separately.
+    /// Merged builders use the setting of this builder.
+    ///
+    /// Same as
+    /// [`set_unchecked_fetch()`](struct.DispatcherBuilder.html#method.set_unchecked_fetch),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Safety
+    ///
+    /// * The `reads` and `writes` of the `SystemData` of every system must
+    ///   include all resources it fetches.
+    /// * While the dispatcher dispatches, no resource declared by one of its
+    ///   systems may be borrowed outside of it, and no system may borrow a
+    ///   resource which conflicts with the declared accesses of another
+    ///   system, or with its own `SystemData`.
+    ///
+    /// Otherwise, the same resource can be borrowed mutably twice, which is
+    /// undefined behavior instead of a panic.
+    #[cfg(feature = "unchecked-fetch")]
+    pub unsafe fn with_unchecked_fetch(mut self) -> Self {
+        // Safety: guaranteed by the caller.
+        unsafe { self.set_unchecked_fetch() };
+
+        self
+    }
+
+    /// Lets the systems fetch their `SystemData` without the runtime borrow
+    /// checking.
+    ///
+    /// See [`with_unchecked_fetch()`](struct.DispatcherBuilder.html#method.with_unchecked_fetch)
+    /// for details.
+    ///
+    /// # Safety
+    ///
+    /// See [`with_unchecked_fetch()`](struct.DispatcherBuilder.html#method.with_unchecked_fetch).
+    #[cfg(feature = "unchecked-fetch")]
+    pub unsafe fn set_unchecked_fetch(&mut self) {
+        self.unchecked_fetch = true;
     }
 
     /// Prints the equivalent system graph
@@ -380,33 +959,115 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// In the future, this method will
     /// precompute useful information in
     /// order to speed up dispatching.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the dependencies can't be resolved, see
+    /// [`try_build()`](struct.DispatcherBuilder.html#method.try_build).
     pub fn build(self) -> Dispatcher<'a, 'b> {
+        self.try_build().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Builds the `Dispatcher`, failing if
+    ///
+    /// * a dependency names neither a system nor a label,
+    /// * the dependencies and ordering constraints form a cycle, or
+    /// * a system is ordered before a system that precedes it by a barrier.
+    pub fn try_build(mut self) -> Result<Dispatcher<'a, 'b>, BuildError> {
         use crate::dispatch::dispatcher::new_dispatcher;
 
+        let stages = self.build_stages()?;
+
         #[cfg(feature = "parallel")]
-        self.thread_pool
-            .write()
-            .unwrap()
-            .get_or_insert_with(Self::create_thread_pool);
+        self.thread_pool.write(|pool| {
+            pool.get_or_insert_with(Self::create_thread_pool);
+        });
+
+        let instruments = self.take_instruments();
 
         #[cfg(feature = "parallel")]
         let d = new_dispatcher(
-            self.stages_builder.build(),
+            stages,
+            self.graph,
             self.thread_local,
             self.thread_pool,
+            instruments,
         );
 
         #[cfg(not(feature = "parallel"))]
-        let d = new_dispatcher(self.stages_builder.build(), self.thread_local);
+        let d = new_dispatcher(stages, self.graph, self.thread_local, instruments);
 
-        d
+        Ok(d)
     }
 
-    fn next_id(&mut self) -> SystemId {
-        let id = self.current_id;
-        self.current_id += 1;
+    /// Places the systems into stages, in an order satisfying all the
+    /// dependencies.
+    fn build_stages(&mut self) -> Result<Vec<Stage<'a>>, BuildError> {
+        let order = self.graph.resolve(0)?;
+        let mut systems = std::mem::take(&mut self.systems);
+
+        Ok(self.graph.layout(order, |node| {
+            let system = systems.remove(&node.id).unwrap();
 
-        SystemId(id)
+            NamedSystem::new(node.id, node.name.clone(), system)
+        }))
+    }
+
+    /// Builds the dispatcher of the batch or partition `name`, sharing the
+    /// thread pool, trace recorder and error sinks of `self`. Panics it
+    /// catches are forwarded to the dispatcher built from `self`.
+    ///
+    /// Also returns the resources read and written by its systems.
+    fn build_sub_dispatcher(
+        &mut self,
+        mut builder: DispatcherBuilder<'a, 'b>,
+        name: &str,
+    ) -> (Dispatcher<'a, 'b>, Vec<ResourceId>, Vec<ResourceId>) {
+        #[cfg(feature = "parallel")]
+        builder.thread_pool.link(&self.thread_pool);
+        builder.trace_recorder.link(&self.trace_recorder);
+        let sink = builder.error_sink();
+        self.error_sinks.append(&mut builder.error_sinks);
+
+        let mut reads = Vec::new();
+        let mut writes = Vec::new();
+        for node in &builder.graph.nodes {
+            reads.extend(node.access.reads.iter().cloned());
+            writes.extend(node.access.writes.iter().cloned());
+            // merging the accumulated values writes to the resources
+            writes.extend(node.access.accumulates.iter().cloned());
+        }
+        reads.sort();
+        reads.dedup();
+        writes.sort();
+        writes.dedup();
+
+        let mut dispatcher = builder.build();
+        dispatcher.set_batch(name, sink);
+
+        (dispatcher, reads, writes)
+    }
+
+    fn take_instruments(&mut self) -> Instruments {
+        use std::mem::take;
+
+        Instruments {
+            error_sinks: take(&mut self.error_sinks),
+            observers: take(&mut self.observers),
+            panic_policy: self.panic_policy,
+            parent_sink: None,
+            tracer: Tracer::new(take(&mut self.trace_recorder)),
+            unchecked_fetch: self.unchecked_fetch,
+        }
+    }
+
+    /// The sink new `TrySystem`s report their errors to.
+    fn error_sink(&mut self) -> ErrorSink {
+        if self.error_sinks.is_empty() {
+            self.error_sinks.push(ErrorSink::default());
+        }
+
+        self.error_sinks[0].clone()
     }
 
     #[cfg(feature = "parallel")]
@@ -429,27 +1090,30 @@ impl<'b> DispatcherBuilder<'static, 'b> {
     /// It does not allow non-static types and accepts a `World` struct or a
     /// value that can be borrowed as `World`.
     pub fn build_async<R>(
-        self,
+        mut self,
         world: R,
     ) -> crate::dispatch::async_dispatcher::AsyncDispatcher<'b, R> {
         use crate::dispatch::async_dispatcher::new_async;
 
-        self.thread_pool
-            .write()
-            .unwrap()
-            .get_or_insert_with(Self::create_thread_pool);
+        self.thread_pool.write(|pool| {
+            pool.get_or_insert_with(Self::create_thread_pool);
+        });
+
+        let stages = self.build_stages().unwrap_or_else(|e| panic!("{}", e));
+        let instruments = self.take_instruments();
 
         new_async(
             world,
-            self.stages_builder.build(),
+            stages,
             self.thread_local,
             self.thread_pool,
+            instruments,
         )
     }
 }
 
 impl fmt::Debug for DispatcherBuilder<'_, '_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.stages_builder.write_par_seq(f, &self.map)
+        self.graph.write_par_seq(f)
     }
 }
@@ -1,8 +1,19 @@
+use std::{
+    any::Any,
+    sync::{Arc, Mutex},
+};
+
 use smallvec::SmallVec;
 
 use crate::{
-    dispatch::{SendDispatcher, stage::Stage},
-    system::RunNow,
+    dispatch::{
+        SendDispatcher,
+        error::{BuildError, DispatchErrors, ErrorSink},
+        graph::SystemGraph,
+        observer::{CatchPanics, Hooks, Instruments},
+        stage::Stage,
+    },
+    system::{RunNow, System},
     world::World,
 };
 
@@ -25,7 +36,7 @@ impl<'a> Dispatcher<'a, '_> {
         self.inner.setup(world);
 
         for sys in &mut self.thread_local {
-            sys.setup(world);
+            sys.system.setup(world);
         }
     }
 
@@ -37,7 +48,7 @@ impl<'a> Dispatcher<'a, '_> {
         self.inner.dispose(world);
 
         for sys in self.thread_local {
-            sys.dispose(world);
+            sys.system.dispose(world);
         }
     }
 
@@ -89,14 +100,151 @@ impl<'a> Dispatcher<'a, '_> {
     /// Please note that this method assumes that no resource
     /// is currently borrowed. If that's the case, it panics.
     pub fn dispatch_thread_local(&mut self, world: &World) {
+        let trace = self.inner.instruments.tracer.current_frame();
+        let panics = Mutex::default();
+        let hooks = Hooks {
+            observers: &self.inner.instruments.observers,
+            panics: CatchPanics::new(self.inner.instruments.panic_policy, &panics),
+            trace: trace.as_ref(),
+            unchecked_fetch: self.inner.instruments.unchecked_fetch,
+        };
+
         for sys in &mut self.thread_local {
-            sys.run_now(world);
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("thread_local", name = &*sys.name).entered();
+
+            hooks.system(sys, None, |sys| hooks.run_now(&mut **sys, world, false));
         }
+
+        self.inner.extend_errors(world, panics);
+    }
+
+    /// Returns the failures of the last dispatch.
+    ///
+    /// Panics are only caught if a [`PanicPolicy`] was configured using
+    /// [`DispatcherBuilder::with_panic_policy`].
+    ///
+    /// [`DispatcherBuilder::with_panic_policy`]: crate::DispatcherBuilder::with_panic_policy
+    pub fn errors(&self) -> &DispatchErrors {
+        self.inner.errors()
+    }
+
+    /// Takes the failures of the last dispatch, leaving an empty list.
+    pub fn take_errors(&mut self) -> DispatchErrors {
+        self.inner.take_errors()
+    }
+
+    /// Returns the system registered as `name`, if it is a `T`.
+    ///
+    /// The dispatcher only holds the systems as trait objects, so this only
+    /// finds systems added with
+    /// [`with_accessible()`](crate::DispatcherBuilder::with_accessible), or
+    /// systems returning themselves from
+    /// [`System::as_any`](crate::System::as_any) (or
+    /// [`TrySystem::as_any`](crate::TrySystem::as_any)), which returns `None`
+    /// by default. Since `Any` is only implemented for `'static` types,
+    /// systems borrowing data can't be accessed. Use
+    /// [`thread_local_system()`](Dispatcher::thread_local_system) for thread
+    /// local systems, which are not registered under a name.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// # use shred::{DispatcherBuilder, System};
+    /// struct Wander {
+    ///     speed: f32,
+    /// }
+    ///
+    /// impl<'a> System<'a> for Wander {
+    ///     type SystemData = ();
+    ///
+    ///     fn run(&mut self, _: ()) {}
+    /// }
+    ///
+    /// let mut dispatcher = DispatcherBuilder::new()
+    ///     .with_accessible(Wander { speed: 1.0 }, "wander", &[])
+    ///     .build();
+    ///
+    /// dispatcher.system_mut::<Wander>("wander").unwrap().speed = 2.0;
+    /// assert_eq!(dispatcher.system::<Wander>("wander").unwrap().speed, 2.0);
+    /// ```
+    pub fn system<T>(&self, name: &str) -> Option<&T>
+    where
+        T: Any,
+    {
+        self.inner.system(name)
+    }
+
+    /// Returns the system registered as `name` mutably, if it is a `T`.
+    ///
+    /// See [`system()`](Dispatcher::system) for details, systems not added
+    /// with `with_accessible()` have to implement
+    /// [`System::as_any_mut`](crate::System::as_any_mut).
+    pub fn system_mut<T>(&mut self, name: &str) -> Option<&mut T>
+    where
+        T: Any,
+    {
+        self.inner.system_mut(name)
+    }
+
+    /// Returns the first thread local system which is a `T`.
+    ///
+    /// Like [`system()`](Dispatcher::system), this only finds systems added
+    /// with
+    /// [`with_thread_local_accessible()`](crate::DispatcherBuilder::with_thread_local_accessible)
+    /// or implementing [`RunNow::as_any`](crate::RunNow::as_any) (which
+    /// forwards to [`System::as_any`](crate::System::as_any) for systems).
+    pub fn thread_local_system<T>(&self) -> Option<&T>
+    where
+        T: Any,
+    {
+        self.thread_local
+            .iter()
+            .find_map(|sys| sys.system.as_any()?.downcast_ref())
+    }
+
+    /// Returns the first thread local system which is a `T` mutably.
+    ///
+    /// See [`thread_local_system()`](Dispatcher::thread_local_system) for
+    /// details, systems not added with `with_thread_local_accessible()` have
+    /// to implement [`RunNow::as_any_mut`](crate::RunNow::as_any_mut).
+    pub fn thread_local_system_mut<T>(&mut self) -> Option<&mut T>
+    where
+        T: Any,
+    {
+        self.thread_local
+            .iter_mut()
+            .find_map(|sys| sys.system.as_any_mut()?.downcast_mut())
+    }
+
+    /// Adds a system to the built dispatcher, setting it up with `world`.
+    ///
+    /// See [`SendDispatcher::insert_system`] for details.
+    pub fn insert_system<T>(
+        &mut self,
+        world: &mut World,
+        system: T,
+        name: &str,
+        dep: &[&str],
+    ) -> Result<(), BuildError>
+    where
+        T: for<'c> System<'c> + Send + 'a,
+    {
+        self.inner.insert_system(world, system, name, dep)
+    }
+
+    /// Removes the system registered as `name` and disposes it, returning
+    /// `false` if there is no such system.
+    ///
+    /// See [`SendDispatcher::remove_system`] for details.
+    pub fn remove_system(&mut self, world: &mut World, name: &str) -> bool {
+        self.inner.remove_system(world, name)
     }
 
     /// Converts this to a [`SendDispatcher`].
     ///
     /// Fails and returns the original distpatcher if it contains thread local systems.
+    #[allow(clippy::result_large_err)]
     pub fn try_into_sendable(self) -> Result<SendDispatcher<'a>, Self> {
         let Dispatcher {
             inner: _,
@@ -117,6 +265,14 @@ impl<'a> Dispatcher<'a, '_> {
     pub fn max_threads(&self) -> usize {
         self.inner.max_threads()
     }
+
+    /// Configures this dispatcher to run as the batch `name`, reporting its
+    /// panics to `parent_sink`.
+    pub(crate) fn set_batch(&mut self, name: &str, parent_sink: ErrorSink) {
+        self.inner.instruments.error_sinks.clear();
+        self.inner.instruments.parent_sink = Some(parent_sink);
+        self.inner.instruments.tracer.label = Some(name.into());
+    }
 }
 
 impl RunNow<'_> for Dispatcher<'_, '_> {
@@ -133,22 +289,51 @@ impl RunNow<'_> for Dispatcher<'_, '_> {
     }
 }
 
+/// Identifies a system added to a [`DispatcherBuilder`], in order of
+/// registration.
+///
+/// [`DispatcherBuilder`]: crate::DispatcherBuilder
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct SystemId(pub usize);
 
+/// A system together with the name it is referred to by (e.g. in traces).
+pub struct NamedSystem<S> {
+    pub id: SystemId,
+    pub name: Arc<str>,
+    pub system: S,
+    /// Set if the system panicked under `PanicPolicy::Disable`.
+    pub disabled: bool,
+}
+
+impl<S> NamedSystem<S> {
+    pub fn new(id: SystemId, name: Arc<str>, system: S) -> Self {
+        NamedSystem {
+            id,
+            name,
+            system,
+            disabled: false,
+        }
+    }
+}
+
 pub type SystemExecSend<'b> = Box<dyn for<'a> RunNow<'a> + Send + 'b>;
-pub type ThreadLocal<'a> = SmallVec<[Box<dyn for<'b> RunNow<'b> + 'a>; 4]>;
+pub type ThreadLocal<'a> = SmallVec<[NamedSystem<Box<dyn for<'b> RunNow<'b> + 'a>>; 4]>;
 
 #[cfg(feature = "parallel")]
 pub fn new_dispatcher<'a, 'b>(
     stages: Vec<Stage<'a>>,
+    graph: SystemGraph,
     thread_local: ThreadLocal<'b>,
-    thread_pool: ::std::sync::Arc<::std::sync::RwLock<ThreadPoolWrapper>>,
+    thread_pool: crate::dispatch::util::SharedSlot<ThreadPoolWrapper>,
+    instruments: Instruments,
 ) -> Dispatcher<'a, 'b> {
     Dispatcher {
         inner: SendDispatcher {
             stages,
+            graph,
             thread_pool,
+            instruments,
+            errors: Default::default(),
         },
         thread_local,
     }
@@ -157,17 +342,28 @@ pub fn new_dispatcher<'a, 'b>(
 #[cfg(not(feature = "parallel"))]
 pub fn new_dispatcher<'a, 'b>(
     stages: Vec<Stage<'a>>,
+    graph: SystemGraph,
     thread_local: ThreadLocal<'b>,
+    instruments: Instruments,
 ) -> Dispatcher<'a, 'b> {
     Dispatcher {
-        inner: SendDispatcher { stages },
+        inner: SendDispatcher {
+            stages,
+            graph,
+            instruments,
+            errors: Default::default(),
+        },
         thread_local,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{dispatch::builder::DispatcherBuilder, system::*, world::*};
+    use crate::{
+        dispatch::{builder::DispatcherBuilder, error::PanicPolicy},
+        system::*,
+        world::*,
+    };
 
     #[derive(Default)]
     struct Res(i32);
@@ -229,6 +425,67 @@ mod tests {
             .dispatch(&new_world())
     }
 
+    #[test]
+    fn panic_policy_continue() {
+        let world = new_world();
+        let mut d = DispatcherBuilder::new()
+            .with(Panic, "p", &[])
+            .with(Dummy(1), "1", &[])
+            .with_panic_policy(PanicPolicy::Continue)
+            .build();
+
+        for _ in 0..2 {
+            d.dispatch(&world);
+
+            let errors = d.errors();
+            assert_eq!(errors.panics().len(), 1);
+            assert_eq!(errors.panics()[0].name(), "p");
+            assert_eq!(errors.panics()[0].message(), Some("Propagated panic"));
+        }
+        assert_eq!(world.fetch::<Res>().0, 2);
+    }
+
+    #[test]
+    fn panic_policy_disable() {
+        let world = new_world();
+        let mut d = DispatcherBuilder::new()
+            .with(Panic, "p", &[])
+            .with_thread_local(Panic)
+            .with_panic_policy(PanicPolicy::Disable)
+            .build();
+
+        d.dispatch(&world);
+        let names = d
+            .take_errors()
+            .take_panics()
+            .into_iter()
+            .map(|panic| panic.name().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["p", "Panic"]);
+
+        d.dispatch(&world);
+        assert!(d.errors().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Systems panicked during dispatch: `p` (Propagated panic)")]
+    fn panic_policy_propagate() {
+        let world = new_world();
+        let mut d = DispatcherBuilder::new()
+            .with(Panic, "p", &[])
+            .with(Dummy(1), "1", &[])
+            .with_panic_policy(PanicPolicy::Propagate)
+            .build();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            d.dispatch(&world);
+        }));
+        assert_eq!(world.fetch::<Res>().0, 1);
+        assert_eq!(d.errors().panics().len(), 1);
+
+        std::panic::resume_unwind(result.unwrap_err());
+    }
+
     #[test]
     fn stages() {
         let mut d = new_builder().build();
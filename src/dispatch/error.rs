@@ -0,0 +1,344 @@
+use std::{
+    any::Any,
+    error::Error,
+    fmt,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use crate::{
+    dispatch::dispatcher::SystemId,
+    system::{RunningTime, System, TrySystem},
+    world::World,
+};
+
+/// Decides what happens after a system panicked, once panic isolation is
+/// enabled using
+/// [`DispatcherBuilder::with_panic_policy`](crate::DispatcherBuilder::with_panic_policy).
+///
+/// With every policy, the other systems of the dispatch still run and the
+/// panic is recorded in the [`DispatchErrors`] of the dispatcher.
+///
+/// Note that resources a system was writing to when it panicked may have been
+/// left in an inconsistent state.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PanicPolicy {
+    /// Panics again once the dispatch is complete, naming all the systems
+    /// which panicked.
+    Propagate,
+    /// Stops running a system after it panicked once.
+    Disable,
+    /// Keeps running systems, even if they panicked before.
+    Continue,
+}
+
+/// A panic caught while running a system.
+pub struct SystemPanic {
+    id: SystemId,
+    name: Arc<str>,
+    payload: Box<dyn Any + Send>,
+}
+
+impl SystemPanic {
+    pub(crate) fn new(id: SystemId, name: Arc<str>, payload: Box<dyn Any + Send>) -> Self {
+        SystemPanic { id, name, payload }
+    }
+
+    /// The id of the system which panicked.
+    pub fn id(&self) -> SystemId {
+        self.id
+    }
+
+    /// The name of the system which panicked.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The panic message, if the payload is a string (which it is for
+    /// `panic!` with a message).
+    pub fn message(&self) -> Option<&str> {
+        self.payload
+            .downcast_ref::<&'static str>()
+            .copied()
+            .or_else(|| self.payload.downcast_ref::<String>().map(String::as_str))
+    }
+
+    /// The payload the system panicked with.
+    pub fn payload(&self) -> &(dyn Any + Send) {
+        &*self.payload
+    }
+
+    /// Returns the payload, e.g. to resume unwinding with it.
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        self.payload
+    }
+}
+
+impl fmt::Debug for SystemPanic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SystemPanic")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+/// An error returned by a [`TrySystem`].
+#[derive(Debug)]
+pub struct SystemError {
+    error: Box<dyn Error + Send + Sync>,
+    id: SystemId,
+    name: Arc<str>,
+}
+
+impl SystemError {
+    /// The id of the system which failed.
+    pub fn id(&self) -> SystemId {
+        self.id
+    }
+
+    /// The name of the system which failed.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The error returned by the system.
+    pub fn error(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.error
+    }
+
+    /// Returns the error returned by the system.
+    pub fn into_error(self) -> Box<dyn Error + Send + Sync> {
+        self.error
+    }
+}
+
+impl fmt::Display for SystemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "System `{}` failed: {}", self.name, self.error)
+    }
+}
+
+impl Error for SystemError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+/// A resource which receives the errors of [`TrySystem`]s.
+///
+/// If the `World` contains an `ErrorHandler`, the errors of a dispatch are
+/// passed to it once the dispatch is complete, instead of being stored in the
+/// [`DispatchErrors`] of the dispatcher.
+pub struct ErrorHandler {
+    handler: Box<dyn FnMut(SystemError) + Send + Sync>,
+}
+
+impl ErrorHandler {
+    /// Creates a handler calling `handler` for every error.
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: FnMut(SystemError) + Send + Sync + 'static,
+    {
+        ErrorHandler {
+            handler: Box::new(handler),
+        }
+    }
+
+    /// Handles a single error.
+    pub fn handle(&mut self, error: SystemError) {
+        (self.handler)(error)
+    }
+}
+
+/// The errors returned by `TrySystem`s and the panics caught by batches,
+/// shared by the systems of a dispatcher and its batches.
+pub type ErrorSink = Arc<Mutex<DispatchErrors>>;
+
+/// Runs a `TrySystem` as a `System`, recording its errors in `sink`.
+pub struct TryRunner<T> {
+    /// Shifted when the builder of the system is merged into another one.
+    pub id: Arc<AtomicUsize>,
+    pub name: Arc<str>,
+    pub sink: ErrorSink,
+    pub system: T,
+}
+
+impl<'a, T> System<'a> for TryRunner<T>
+where
+    T: TrySystem<'a>,
+{
+    type SystemData = T::SystemData;
+
+    fn run(&mut self, data: Self::SystemData) {
+        if let Err(error) = self.system.run(data) {
+            self.sink.lock().unwrap().errors.push(SystemError {
+                error: error.into(),
+                id: SystemId(self.id.load(Ordering::Relaxed)),
+                name: self.name.clone(),
+            });
+        }
+    }
+
+    fn running_time(&self) -> RunningTime {
+        self.system.running_time()
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        self.system.setup(world);
+    }
+
+    fn dispose(self, world: &mut World) {
+        self.system.dispose(world);
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        self.system.as_any()
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        self.system.as_any_mut()
+    }
+}
+
+/// The failures of the last dispatch.
+///
+/// `dispatch`, `dispatch_par` and `dispatch_seq` reset the list, failures of
+/// thread local systems are added to it.
+#[derive(Debug, Default)]
+pub struct DispatchErrors {
+    pub(crate) errors: Vec<SystemError>,
+    pub(crate) panics: Vec<SystemPanic>,
+}
+
+impl DispatchErrors {
+    /// Returns `true` if all systems ran successfully.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.panics.is_empty()
+    }
+
+    /// The errors returned by `TrySystem`s during the dispatch, in no
+    /// particular order.
+    ///
+    /// Always empty if the `World` has an [`ErrorHandler`].
+    pub fn errors(&self) -> &[SystemError] {
+        &self.errors
+    }
+
+    /// Takes the errors out of the list.
+    pub fn take_errors(&mut self) -> Vec<SystemError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// The panics caught during the dispatch, in no particular order.
+    pub fn panics(&self) -> &[SystemPanic] {
+        &self.panics
+    }
+
+    /// Takes the caught panics out of the list.
+    pub fn take_panics(&mut self) -> Vec<SystemPanic> {
+        std::mem::take(&mut self.panics)
+    }
+}
+
+/// Moves the failures collected in `sink` to `errors`, passing the errors to
+/// the `ErrorHandler` of `world` instead if there is one.
+pub(crate) fn drain_sink(sink: &ErrorSink, world: &World, errors: &mut DispatchErrors) {
+    let mut sink = sink.lock().unwrap();
+    errors.panics.append(&mut sink.panics);
+    if sink.errors.is_empty() {
+        return;
+    }
+
+    match world.try_fetch_mut::<ErrorHandler>() {
+        Some(mut handler) => sink.errors.drain(..).for_each(|e| handler.handle(e)),
+        None => errors.errors.append(&mut sink.errors),
+    }
+}
+
+/// Moves the `panics` caught by a batch to the `parent` sink, so they are
+/// reported by the dispatcher running the batch, unless the batch propagates
+/// them itself.
+pub(crate) fn forward(
+    policy: Option<PanicPolicy>,
+    parent: &ErrorSink,
+    panics: &mut Vec<SystemPanic>,
+) {
+    if policy != Some(PanicPolicy::Propagate) && !panics.is_empty() {
+        parent.lock().unwrap().panics.append(panics);
+    }
+}
+
+/// The reason a [`DispatcherBuilder`](crate::DispatcherBuilder) couldn't
+/// build a dispatcher or merge another builder.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BuildError {
+    /// `system` depends on `dependency`, which is neither the name of a
+    /// system nor a label.
+    UnknownDependency {
+        /// The name of the depending system.
+        system: String,
+        /// The dependency which couldn't be found.
+        dependency: String,
+    },
+    /// Systems with these names were registered in both merged builders.
+    DuplicateNames(Vec<String>),
+    /// The dependencies form a cycle; the path starts and ends with the same
+    /// system and each system has to run before the next one.
+    Cycle(Vec<String>),
+    /// `before` has to run before `after`, but `after` was added before a
+    /// barrier that `before` was added after.
+    Barrier {
+        /// The system which has to run first.
+        before: String,
+        /// The system which has to run second.
+        after: String,
+    },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::UnknownDependency { system, dependency } => write!(
+                f,
+                "No such system registered (\"{}\"), required by \"{}\"",
+                dependency, system
+            ),
+            BuildError::DuplicateNames(names) => write!(
+                f,
+                "Cannot insert multiple systems with the same name (\"{}\")",
+                names.join("\", \"")
+            ),
+            BuildError::Cycle(path) => {
+                write!(f, "Systems depend on each other: {}", path.join(" -> "))
+            }
+            BuildError::Barrier { before, after } => write!(
+                f,
+                "\"{}\" has to run before \"{}\", but a barrier separates them the other way",
+                before, after
+            ),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// Panics if `policy` says so and there are new `panics`.
+pub(crate) fn propagate(policy: Option<PanicPolicy>, panics: &[SystemPanic]) {
+    if policy != Some(PanicPolicy::Propagate) || panics.is_empty() {
+        return;
+    }
+
+    let names = panics
+        .iter()
+        .map(|panic| match panic.message() {
+            Some(message) => format!("`{}` ({})", panic.name, message),
+            None => format!("`{}`", panic.name),
+        })
+        .collect::<Vec<_>>();
+
+    panic!("Systems panicked during dispatch: {}", names.join(", "));
+}
@@ -0,0 +1,342 @@
+//! The dependency graph of the systems of a dispatcher, which decides the
+//! order they are placed into stages in.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, hash_map::Entry},
+    fmt,
+    sync::Arc,
+};
+
+use ahash::AHashMap as HashMap;
+use smallvec::SmallVec;
+
+use crate::dispatch::{
+    dispatcher::{NamedSystem, SystemExecSend, SystemId},
+    error::BuildError,
+    stage::{Stage, StagesBuilder, SystemAccess},
+};
+
+/// The index of a node together with its direct dependencies.
+pub type Resolved = (usize, SmallVec<[SystemId; 4]>);
+
+/// Everything about a system except the system itself.
+pub struct SystemNode {
+    pub access: SystemAccess,
+    /// The systems or labels this system depends on.
+    pub after: Vec<String>,
+    pub barrier: usize,
+    pub id: SystemId,
+    pub labels: Vec<String>,
+    pub name: Arc<str>,
+}
+
+#[derive(Default)]
+pub struct SystemGraph {
+    /// The number of barriers added so far.
+    pub barrier: usize,
+    current_id: usize,
+    /// Maps the names of the systems to their ids.
+    pub map: HashMap<String, SystemId>,
+    /// The systems in order of registration.
+    pub nodes: Vec<SystemNode>,
+    /// `(before, after)` constraints between systems or labels.
+    pub order: Vec<(String, String)>,
+}
+
+impl SystemGraph {
+    pub fn next_id(&mut self) -> SystemId {
+        let id = self.current_id;
+        self.current_id += 1;
+
+        SystemId(id)
+    }
+
+    /// Adds a system after all the others, registering it as `name` unless
+    /// `name` is empty.
+    pub fn add(
+        &mut self,
+        id: SystemId,
+        name: &str,
+        display_name: Arc<str>,
+        access: SystemAccess,
+        labels: &[&str],
+        dep: &[&str],
+    ) -> Result<(), BuildError> {
+        if !name.is_empty() {
+            match self.map.entry(name.to_owned()) {
+                Entry::Vacant(e) => e.insert(id),
+                Entry::Occupied(_) => {
+                    return Err(BuildError::DuplicateNames(vec![name.to_owned()]));
+                }
+            };
+        }
+
+        self.nodes.push(SystemNode {
+            access,
+            after: dep.iter().map(|x| (*x).to_owned()).collect(),
+            barrier: self.barrier,
+            id,
+            labels: labels.iter().map(|x| (*x).to_owned()).collect(),
+            name: display_name,
+        });
+
+        Ok(())
+    }
+
+    /// Removes the system registered as `name`.
+    pub fn remove(&mut self, name: &str) -> Option<SystemId> {
+        let id = self.map.remove(name)?;
+        self.remove_id(id);
+
+        Some(id)
+    }
+
+    /// Removes the system with the given id, which may be unnamed.
+    pub fn remove_id(&mut self, id: SystemId) {
+        self.map.retain(|_, other| *other != id);
+        self.nodes.retain(|node| node.id != id);
+    }
+
+    pub fn node_mut(&mut self, id: SystemId) -> &mut SystemNode {
+        self.nodes.iter_mut().find(|node| node.id == id).unwrap()
+    }
+
+    /// Adds the systems of `other` after the systems of this graph, returning
+    /// the offset added to their ids.
+    pub fn merge(&mut self, other: SystemGraph) -> Result<usize, BuildError> {
+        let mut clashes = other
+            .map
+            .keys()
+            .filter(|name| self.map.contains_key(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !clashes.is_empty() {
+            clashes.sort();
+
+            return Err(BuildError::DuplicateNames(clashes));
+        }
+
+        let offset = self.current_id;
+        let barrier = self.barrier;
+
+        self.map.extend(
+            other
+                .map
+                .into_iter()
+                .map(|(name, id)| (name, SystemId(id.0 + offset))),
+        );
+        self.nodes
+            .extend(other.nodes.into_iter().map(|node| SystemNode {
+                barrier: node.barrier + barrier,
+                id: SystemId(node.id.0 + offset),
+                ..node
+            }));
+        self.order.extend(other.order);
+        self.current_id += other.current_id;
+        self.barrier += other.barrier;
+
+        Ok(offset)
+    }
+
+    /// Sorts the systems topologically, returning the index of every system
+    /// together with its direct dependencies.
+    ///
+    /// Systems are kept in order of registration as far as possible, so
+    /// builders without forward references are laid out like they always
+    /// were.
+    ///
+    /// Unknown dependencies of the systems starting at index `check_from` are
+    /// errors, the ones of earlier systems (whose dependency has been removed
+    /// from a built dispatcher) are ignored.
+    pub fn resolve(&self, check_from: usize) -> Result<Vec<Resolved>, BuildError> {
+        let systems = &self.nodes;
+
+        let indices: HashMap<SystemId, usize> = systems
+            .iter()
+            .enumerate()
+            .map(|(index, system)| (system.id, index))
+            .collect();
+        let mut labels: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, system) in systems.iter().enumerate() {
+            for label in &system.labels {
+                labels.entry(label).or_default().push(index);
+            }
+        }
+        let by_name = |name: &str| self.map.get(name).and_then(|id| indices.get(id)).copied();
+        let lookup = |name: &str| {
+            by_name(name)
+                .into_iter()
+                .chain(labels.get(name).into_iter().flatten().copied())
+                .collect::<SmallVec<[usize; 4]>>()
+        };
+
+        // Edges from a system to itself are cycles if both ends name the
+        // system, but are dropped if they only come from a label, e.g. a
+        // system in both sets ordered by `with_set_order`.
+        let mut preds = vec![SmallVec::<[usize; 4]>::new(); systems.len()];
+        for (index, system) in systems.iter().enumerate() {
+            for dep in &system.after {
+                let found = lookup(dep);
+                if found.is_empty() && index >= check_from {
+                    return Err(BuildError::UnknownDependency {
+                        system: system.name.to_string(),
+                        dependency: dep.clone(),
+                    });
+                }
+
+                let explicit = by_name(dep) == Some(index);
+                preds[index].extend(found.into_iter().filter(|&pred| pred != index || explicit));
+            }
+        }
+        for (before, after) in &self.order {
+            let explicit = by_name(before).filter(|&index| by_name(after) == Some(index));
+            let before = lookup(before);
+            for after in lookup(after) {
+                preds[after].extend(
+                    before
+                        .iter()
+                        .copied()
+                        .filter(|&pred| pred != after || explicit == Some(after)),
+                );
+            }
+        }
+        for (index, preds) in preds.iter_mut().enumerate() {
+            preds.sort_unstable();
+            preds.dedup();
+
+            if let Some(&pred) = preds
+                .iter()
+                .find(|&&pred| systems[pred].barrier > systems[index].barrier)
+            {
+                return Err(BuildError::Barrier {
+                    before: systems[pred].name.to_string(),
+                    after: systems[index].name.to_string(),
+                });
+            }
+        }
+
+        // Kahn's algorithm, always picking the earliest registered system
+        let mut in_degree = preds.iter().map(|preds| preds.len()).collect::<Vec<_>>();
+        let mut succs = vec![Vec::new(); systems.len()];
+        for (index, preds) in preds.iter().enumerate() {
+            for &pred in preds {
+                succs[pred].push(index);
+            }
+        }
+        let mut ready = (0..systems.len())
+            .filter(|&index| in_degree[index] == 0)
+            .map(|index| Reverse((systems[index].barrier, index)))
+            .collect::<BinaryHeap<_>>();
+        let mut order = Vec::with_capacity(systems.len());
+        while let Some(Reverse((_, index))) = ready.pop() {
+            order.push(index);
+
+            for &succ in &succs[index] {
+                in_degree[succ] -= 1;
+                if in_degree[succ] == 0 {
+                    ready.push(Reverse((systems[succ].barrier, succ)));
+                }
+            }
+        }
+
+        if order.len() < systems.len() {
+            // every system left over waits for another left over system, so
+            // walking backwards through them has to end up in a cycle
+            let mut path: Vec<usize> = Vec::new();
+            let mut current = (0..systems.len())
+                .find(|&index| in_degree[index] > 0)
+                .unwrap();
+            while !path.contains(&current) {
+                path.push(current);
+                current = *preds[current]
+                    .iter()
+                    .find(|&&pred| in_degree[pred] > 0)
+                    .unwrap();
+            }
+
+            let start = path.iter().position(|&index| index == current).unwrap();
+            let mut cycle = path.split_off(start);
+            cycle.reverse();
+            // start with the earliest registered system
+            let first = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+            cycle.rotate_left(first);
+            cycle.push(cycle[0]);
+            let cycle = cycle
+                .into_iter()
+                .map(|index| systems[index].name.to_string())
+                .collect();
+
+            return Err(BuildError::Cycle(cycle));
+        }
+
+        Ok(order
+            .into_iter()
+            .map(|index| {
+                let deps = preds[index].iter().map(|&pred| systems[pred].id).collect();
+
+                (index, deps)
+            })
+            .collect())
+    }
+
+    /// Places the systems into stages in the given `order`, `system` returns
+    /// the system of a node.
+    pub fn layout<'a, F>(&self, order: Vec<Resolved>, mut system: F) -> Vec<Stage<'a>>
+    where
+        F: FnMut(&SystemNode) -> NamedSystem<SystemExecSend<'a>>,
+    {
+        let mut stages_builder = StagesBuilder::default();
+        let mut barrier = 0;
+        for (index, dependencies) in order {
+            let node = &self.nodes[index];
+            if node.barrier != barrier {
+                barrier = node.barrier;
+                stages_builder.add_barrier();
+            }
+
+            stages_builder.insert_boxed(dependencies, &node.access, system(node));
+        }
+
+        stages_builder.build()
+    }
+
+    /// Writes the layout of the systems as `seq!`/`par!` macros.
+    pub fn write_par_seq(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let order = match self.resolve(0) {
+            Ok(order) => order,
+            Err(e) => return write!(f, "{}", e),
+        };
+
+        // lay out the stages without the systems
+        let mut stages_builder = StagesBuilder::default();
+        let mut barrier = 0;
+        for (index, dependencies) in order {
+            let node = &self.nodes[index];
+            if node.barrier != barrier {
+                barrier = node.barrier;
+                stages_builder.add_barrier();
+            }
+
+            stages_builder.place(dependencies, node.id, &node.access);
+        }
+
+        let names = self
+            .nodes
+            .iter()
+            .map(|node| (node.id, &*node.name))
+            .collect();
+
+        stages_builder.write_par_seq(f, &names)
+    }
+}
+
+/// The name used for `T` in traces and errors; its type name if it has been
+/// added anonymously.
+pub fn system_name<T>(name: &str) -> Arc<str> {
+    if name.is_empty() {
+        tynm::type_name::<T>().into()
+    } else {
+        name.into()
+    }
+}
@@ -4,12 +4,17 @@ pub use self::async_dispatcher::AsyncDispatcher;
 pub use self::par_seq::{Par, ParSeq, RunWithPool, Seq};
 pub use self::{
     batch::{
-        BatchAccessor, BatchController, BatchUncheckedWorld, MultiDispatchController,
-        MultiDispatcher,
+        BatchAccessor, BatchController, BatchFetch, BatchUncheckedWorld, BatchWorld, Converge,
+        ConvergeController, Convergence, MultiDispatchController, MultiDispatcher,
     },
     builder::DispatcherBuilder,
-    dispatcher::Dispatcher,
+    dispatcher::{Dispatcher, SystemId},
+    error::{BuildError, DispatchErrors, ErrorHandler, PanicPolicy, SystemError, SystemPanic},
+    observer::DispatchObserver,
+    partition::{PartitionAccessor, PartitionData},
+    rate::{DeltaTime, Every, FixedTimestep},
     send_dispatcher::SendDispatcher,
+    trace::TraceRecorder,
 };
 
 #[cfg(feature = "parallel")]
@@ -17,8 +22,14 @@ mod async_dispatcher;
 mod batch;
 mod builder;
 mod dispatcher;
+mod error;
+mod graph;
+mod observer;
 #[cfg(feature = "parallel")]
 mod par_seq;
+mod partition;
+mod rate;
 mod send_dispatcher;
 mod stage;
+mod trace;
 mod util;
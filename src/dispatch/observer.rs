@@ -0,0 +1,248 @@
+use std::{
+    panic::{AssertUnwindSafe, catch_unwind},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    RunNow, World,
+    dispatch::{
+        dispatcher::{NamedSystem, SystemId},
+        error::{ErrorSink, PanicPolicy, SystemPanic},
+        trace::{TraceFrame, Tracer},
+    },
+    world::unchecked,
+};
+
+/// Receives callbacks while a dispatcher executes its systems.
+///
+/// Observers are registered using
+/// [`DispatcherBuilder::with_observer`](crate::DispatcherBuilder::with_observer)
+/// and are the extension point for profilers, loggers, frame debuggers or
+/// test harnesses. All methods have empty default implementations, so only
+/// the interesting ones need to be overridden.
+///
+/// Systems of the same stage run in parallel, so the system callbacks may be
+/// invoked concurrently from several threads. Thread local systems are
+/// reported after `dispatch_end`, without a stage.
+///
+/// Observers are not inherited by batches; register them on the
+/// `DispatcherBuilder` of the batch to observe its sub-dispatches.
+///
+/// ## Examples
+///
+/// ```
+/// # use std::sync::{
+/// #     atomic::{AtomicUsize, Ordering},
+/// #     Arc,
+/// # };
+/// # use shred::{DispatchObserver, DispatcherBuilder, System, SystemId, World};
+/// # struct Sys;
+/// # impl<'a> System<'a> for Sys {
+/// #     type SystemData = ();
+/// #     fn run(&mut self, _: ()) {}
+/// # }
+/// #[derive(Default)]
+/// struct CountRuns(AtomicUsize);
+///
+/// impl DispatchObserver for CountRuns {
+///     fn after_system(&self, _name: &str, _id: SystemId) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let runs = Arc::new(CountRuns::default());
+/// let mut dispatcher = DispatcherBuilder::new()
+///     .with(Sys, "sys", &[])
+///     .with_observer(runs.clone())
+///     .build();
+///
+/// dispatcher.dispatch(&World::empty());
+/// assert_eq!(runs.0.load(Ordering::Relaxed), 1);
+/// ```
+pub trait DispatchObserver: Send + Sync {
+    /// Called before the first stage is executed.
+    fn dispatch_start(&self) {}
+
+    /// Called after the last stage has been executed.
+    fn dispatch_end(&self) {}
+
+    /// Called before the systems of `stage` are executed.
+    fn stage_start(&self, _stage: usize) {}
+
+    /// Called after all systems of `stage` have finished.
+    fn stage_end(&self, _stage: usize) {}
+
+    /// Called right before the system registered as `name` is run.
+    fn before_system(&self, _name: &str, _id: SystemId) {}
+
+    /// Called right after the system registered as `name` has run.
+    fn after_system(&self, _name: &str, _id: SystemId) {}
+}
+
+impl<T> DispatchObserver for Arc<T>
+where
+    T: DispatchObserver + ?Sized,
+{
+    fn dispatch_start(&self) {
+        T::dispatch_start(self)
+    }
+
+    fn dispatch_end(&self) {
+        T::dispatch_end(self)
+    }
+
+    fn stage_start(&self, stage: usize) {
+        T::stage_start(self, stage)
+    }
+
+    fn stage_end(&self, stage: usize) {
+        T::stage_end(self, stage)
+    }
+
+    fn before_system(&self, name: &str, id: SystemId) {
+        T::before_system(self, name, id)
+    }
+
+    fn after_system(&self, name: &str, id: SystemId) {
+        T::after_system(self, name, id)
+    }
+}
+
+pub type Observers = Vec<Box<dyn DispatchObserver>>;
+
+/// The optional machinery attached to a dispatcher: tracing, observers and
+/// error collection.
+#[derive(Default)]
+pub struct Instruments {
+    /// Empty for batches, whose errors are collected by the parent.
+    pub error_sinks: Vec<ErrorSink>,
+    pub observers: Observers,
+    pub panic_policy: Option<PanicPolicy>,
+    /// The sink of the dispatcher running this batch, which the caught panics
+    /// are forwarded to.
+    pub parent_sink: Option<ErrorSink>,
+    pub tracer: Tracer,
+    /// Whether the system data may skip the borrow flags, see
+    /// `DispatcherBuilder::with_unchecked_fetch`.
+    pub unchecked_fetch: bool,
+}
+
+/// Everything that wants to know about the execution of one dispatch.
+#[derive(Clone, Copy)]
+pub struct Hooks<'h> {
+    pub observers: &'h [Box<dyn DispatchObserver>],
+    pub panics: Option<CatchPanics<'h>>,
+    pub trace: Option<&'h TraceFrame>,
+    pub unchecked_fetch: bool,
+}
+
+/// Where to put the panics of systems if panic isolation is enabled.
+#[derive(Clone, Copy)]
+pub struct CatchPanics<'h> {
+    pub panics: &'h Mutex<Vec<SystemPanic>>,
+    pub policy: PanicPolicy,
+}
+
+impl<'h> CatchPanics<'h> {
+    /// Returns `None` if panic isolation is disabled.
+    pub fn new(policy: Option<PanicPolicy>, panics: &'h Mutex<Vec<SystemPanic>>) -> Option<Self> {
+        policy.map(|policy| CatchPanics { panics, policy })
+    }
+}
+
+impl Hooks<'_> {
+    pub fn dispatch<R>(&self, f: impl FnOnce() -> R) -> R {
+        for observer in self.observers {
+            observer.dispatch_start();
+        }
+
+        let r = match self.trace {
+            Some(trace) => trace.dispatch(f),
+            None => f(),
+        };
+
+        for observer in self.observers {
+            observer.dispatch_end();
+        }
+
+        r
+    }
+
+    pub fn stage<R>(&self, stage: usize, f: impl FnOnce() -> R) -> R {
+        for observer in self.observers {
+            observer.stage_start(stage);
+        }
+
+        let r = f();
+
+        for observer in self.observers {
+            observer.stage_end(stage);
+        }
+
+        r
+    }
+
+    /// Runs `system` now, skipping the borrow checking of its `SystemData` if
+    /// the dispatcher uses unchecked fetches. If `in_stage` is set, the
+    /// stage merges the accumulated values after all its systems ran.
+    pub fn run_now<'a>(
+        &self,
+        system: &mut (dyn RunNow<'a> + '_),
+        world: &'a World,
+        in_stage: bool,
+    ) {
+        let unchecked = unchecked::ENABLED && self.unchecked_fetch;
+
+        // Safety: `unchecked_fetch` has been enabled unsafely, promising that
+        // the declared accesses of all systems are correct.
+        unsafe { system.run_dispatched(world, unchecked, in_stage) };
+    }
+
+    /// Runs `system` using `f`, unless it has been disabled. `stage` is
+    /// `None` for thread local systems.
+    pub fn system<S>(
+        &self,
+        system: &mut NamedSystem<S>,
+        stage: Option<usize>,
+        f: impl FnOnce(&mut S),
+    ) {
+        if system.disabled {
+            return;
+        }
+
+        let NamedSystem {
+            id,
+            ref name,
+            system: ref mut sys,
+            ref mut disabled,
+        } = *system;
+
+        for observer in self.observers {
+            observer.before_system(name, id);
+        }
+
+        let run = || match (self.trace, stage) {
+            (Some(trace), Some(stage)) => trace.system(name, stage, || f(sys)),
+            (Some(trace), None) => trace.thread_local(name, || f(sys)),
+            (None, _) => f(sys),
+        };
+
+        match self.panics {
+            Some(catch) => {
+                if let Err(payload) = catch_unwind(AssertUnwindSafe(run)) {
+                    *disabled = catch.policy == PanicPolicy::Disable;
+                    catch
+                        .panics
+                        .lock()
+                        .unwrap()
+                        .push(SystemPanic::new(id, name.clone(), payload));
+                }
+            }
+            None => run(),
+        }
+
+        for observer in self.observers {
+            observer.after_system(name, id);
+        }
+    }
+}
@@ -284,19 +284,24 @@ where
     }
 
     fn run(&mut self, world: &'a World, _: &ThreadPool) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("system", name = tynm::type_name::<T>()).entered();
+
         RunNow::run_now(self, world);
     }
 
     fn reads(&self, reads: &mut Vec<ResourceId>) {
         use crate::system::Accessor;
 
-        reads.extend(self.accessor().reads())
+        self.accessor().extend_reads(reads);
     }
 
     fn writes(&self, writes: &mut Vec<ResourceId>) {
         use crate::system::Accessor;
 
-        writes.extend(self.accessor().writes())
+        self.accessor().extend_writes(writes);
+        // accumulated values are merged right after the system ran
+        self.accessor().extend_accumulates(writes);
     }
 }
 
@@ -0,0 +1,424 @@
+use std::sync::Arc;
+
+#[cfg(feature = "parallel")]
+use crate::dispatch::{dispatcher::ThreadPoolWrapper, util::SharedSlot};
+use crate::{
+    dispatch::{BatchAccessor, BatchUncheckedWorld, SendDispatcher},
+    system::{Accessor, AccessorCow, DynamicSystemData, RunningTime, System},
+    world::{Fetch, FetchMut, Resource, ResourceId, World},
+};
+
+/// The resources of a single partition a system accesses.
+///
+/// A partition is the set of resources inserted with the same dynamic id,
+/// see [`ResourceId::new_with_dynamic_id`]. Systems of a partitioned batch
+/// (see
+/// [`add_partitioned`](crate::DispatcherBuilder::add_partitioned)) return it
+/// from [`System::accessor`] and fetch [`PartitionData`].
+///
+/// Systems have to be written against `PartitionData`; systems fetching
+/// `Read` or `Write` can't be bound to a partition. Since `PartitionData` is
+/// the whole `SystemData` of such a system, it can't also access resources
+/// shared by all partitions; these have to be read by other systems of the
+/// sub-dispatcher.
+///
+/// ## Examples
+///
+/// ```
+/// use shred::{AccessorCow, PartitionAccessor, PartitionData, System};
+///
+/// struct Score(u32);
+///
+/// struct CountScore {
+///     access: PartitionAccessor,
+/// }
+///
+/// impl<'a> System<'a> for CountScore {
+///     type SystemData = PartitionData<'a>;
+///
+///     fn run(&mut self, data: Self::SystemData) {
+///         data.fetch_mut::<Score>().0 += 1;
+///     }
+///
+///     fn accessor<'b>(&'b self) -> AccessorCow<'a, 'b, Self> {
+///         AccessorCow::Ref(&self.access)
+///     }
+/// }
+///
+/// let system = CountScore {
+///     access: PartitionAccessor::new(3).write::<Score>(),
+/// };
+/// ```
+#[derive(Clone, Debug)]
+pub struct PartitionAccessor {
+    partition: u64,
+    reads: Arc<[ResourceId]>,
+    writes: Arc<[ResourceId]>,
+}
+
+impl PartitionAccessor {
+    /// Creates an accessor for `partition` which doesn't access any resource
+    /// yet.
+    pub fn new(partition: u64) -> Self {
+        PartitionAccessor {
+            partition,
+            reads: Arc::new([]),
+            writes: Arc::new([]),
+        }
+    }
+
+    /// The dynamic id of the partition.
+    pub fn partition(&self) -> u64 {
+        self.partition
+    }
+
+    /// Declares read access to the `T` of the partition.
+    pub fn read<T: Resource>(mut self) -> Self {
+        self.reads = with_id::<T>(&self.reads, self.partition);
+
+        self
+    }
+
+    /// Declares write access to the `T` of the partition.
+    pub fn write<T: Resource>(mut self) -> Self {
+        self.writes = with_id::<T>(&self.writes, self.partition);
+
+        self
+    }
+}
+
+/// Copies `ids`, adding the id of the `T` of `partition`.
+fn with_id<T: Resource>(ids: &[ResourceId], partition: u64) -> Arc<[ResourceId]> {
+    ids.iter()
+        .cloned()
+        .chain(Some(ResourceId::new_with_dynamic_id::<T>(partition)))
+        .collect()
+}
+
+impl Accessor for PartitionAccessor {
+    fn try_new() -> Option<Self> {
+        None
+    }
+
+    fn reads(&self) -> Vec<ResourceId> {
+        self.reads.to_vec()
+    }
+
+    fn writes(&self) -> Vec<ResourceId> {
+        self.writes.to_vec()
+    }
+}
+
+/// The `SystemData` of a system accessing the resources of a single
+/// partition, declared by its [`PartitionAccessor`].
+pub struct PartitionData<'a> {
+    partition: u64,
+    reads: Arc<[ResourceId]>,
+    world: &'a World,
+    writes: Arc<[ResourceId]>,
+}
+
+impl<'a> PartitionData<'a> {
+    /// The dynamic id of the partition.
+    pub fn partition(&self) -> u64 {
+        self.partition
+    }
+
+    /// Fetches the `T` of the partition.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` wasn't declared by the accessor, if it doesn't exist or
+    /// if it's already borrowed mutably.
+    pub fn fetch<T: Resource>(&self) -> Fetch<'a, T> {
+        let id = self.id::<T>();
+        if !self.reads.contains(&id) && !self.writes.contains(&id) {
+            self.undeclared::<T>();
+        }
+
+        self.world
+            .try_fetch_by_id(id)
+            .unwrap_or_else(|| self.missing::<T>())
+    }
+
+    /// Fetches the `T` of the partition mutably.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` wasn't declared as written by the accessor, if it doesn't
+    /// exist or if it's already borrowed.
+    pub fn fetch_mut<T: Resource>(&self) -> FetchMut<'a, T> {
+        let id = self.id::<T>();
+        if !self.writes.contains(&id) {
+            self.undeclared::<T>();
+        }
+
+        self.world
+            .try_fetch_mut_by_id(id)
+            .unwrap_or_else(|| self.missing::<T>())
+    }
+
+    fn id<T: Resource>(&self) -> ResourceId {
+        ResourceId::new_with_dynamic_id::<T>(self.partition)
+    }
+
+    fn undeclared<T>(&self) -> ! {
+        panic!(
+            "`{}` of partition {} was accessed without being declared by the `PartitionAccessor`",
+            std::any::type_name::<T>(),
+            self.partition
+        )
+    }
+
+    fn missing<T>(&self) -> ! {
+        panic!(
+            "Tried to fetch `{}` of partition {}, but the resource does not exist",
+            std::any::type_name::<T>(),
+            self.partition
+        )
+    }
+}
+
+impl<'a> DynamicSystemData<'a> for PartitionData<'a> {
+    type Accessor = PartitionAccessor;
+
+    fn setup(_accessor: &Self::Accessor, _world: &mut World) {}
+
+    fn fetch(access: &Self::Accessor, world: &'a World) -> Self {
+        PartitionData {
+            partition: access.partition,
+            reads: access.reads.clone(),
+            world,
+            writes: access.writes.clone(),
+        }
+    }
+}
+
+/// Runs a copy of the same sub-dispatcher for every partition.
+pub(crate) struct PartitionSystem<'a> {
+    pub accessor: BatchAccessor,
+    pub dispatchers: Vec<SendDispatcher<'a>>,
+    #[cfg(feature = "parallel")]
+    pub thread_pool: SharedSlot<ThreadPoolWrapper>,
+}
+
+impl<'c> System<'c> for PartitionSystem<'_> {
+    type SystemData = BatchUncheckedWorld<'c>;
+
+    #[cfg(feature = "parallel")]
+    fn run(&mut self, data: Self::SystemData) {
+        use rayon::prelude::*;
+
+        let dispatchers = &mut self.dispatchers;
+        self.thread_pool
+            .read(Clone::clone)
+            .unwrap()
+            .install(|| {
+                dispatchers
+                    .par_iter_mut()
+                    .for_each(|dispatcher| dispatcher.dispatch(data.0))
+            });
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn run(&mut self, data: Self::SystemData) {
+        for dispatcher in &mut self.dispatchers {
+            dispatcher.dispatch(data.0);
+        }
+    }
+
+    fn running_time(&self) -> RunningTime {
+        RunningTime::VeryLong
+    }
+
+    fn accessor<'s>(&'s self) -> AccessorCow<'c, 's, Self> {
+        AccessorCow::Ref(&self.accessor)
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        for dispatcher in &mut self.dispatchers {
+            dispatcher.setup(world);
+        }
+    }
+
+    fn dispose(self, world: &mut World) {
+        for dispatcher in self.dispatchers {
+            dispatcher.dispose(world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PartitionAccessor, PartitionData};
+    use crate::{
+        AccessorCow, DispatcherBuilder, PanicPolicy, ResourceId, System, TrySystem, World,
+    };
+
+    struct Position(i32);
+
+    struct Speed(i32);
+
+    struct Movement {
+        access: PartitionAccessor,
+    }
+
+    impl Movement {
+        fn new(partition: u64) -> Self {
+            Movement {
+                access: PartitionAccessor::new(partition)
+                    .read::<Speed>()
+                    .write::<Position>(),
+            }
+        }
+    }
+
+    impl<'a> System<'a> for Movement {
+        type SystemData = PartitionData<'a>;
+
+        fn run(&mut self, data: Self::SystemData) {
+            let speed = data.fetch::<Speed>().0;
+            data.fetch_mut::<Position>().0 += speed;
+        }
+
+        fn accessor<'b>(&'b self) -> AccessorCow<'a, 'b, Self> {
+            AccessorCow::Ref(&self.access)
+        }
+    }
+
+    fn arenas(partitions: &[u64]) -> World {
+        let mut world = World::empty();
+        for &partition in partitions {
+            world.insert_by_id(
+                ResourceId::new_with_dynamic_id::<Position>(partition),
+                Position(0),
+            );
+            world.insert_by_id(
+                ResourceId::new_with_dynamic_id::<Speed>(partition),
+                Speed(partition as i32),
+            );
+        }
+
+        world
+    }
+
+    fn position(world: &World, partition: u64) -> i32 {
+        world
+            .try_fetch_by_id::<Position>(ResourceId::new_with_dynamic_id::<Position>(partition))
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn partitions() {
+        let partitions = [1, 2, 3];
+        let mut world = arenas(&partitions);
+        let mut dispatcher = DispatcherBuilder::new()
+            .with_partitioned(
+                &partitions,
+                |partition| {
+                    DispatcherBuilder::new().with(Movement::new(partition), "movement", &[])
+                },
+                "arenas",
+                &[],
+            )
+            .build();
+        dispatcher.setup(&mut world);
+
+        dispatcher.dispatch(&world);
+        dispatcher.dispatch(&world);
+
+        assert_eq!(position(&world, 1), 2);
+        assert_eq!(position(&world, 2), 4);
+        assert_eq!(position(&world, 3), 6);
+    }
+
+    /// Failures in partitions are reported by the parent dispatcher.
+    #[test]
+    fn partition_errors() {
+        struct Fail;
+
+        impl<'a> TrySystem<'a> for Fail {
+            type Error = String;
+            type SystemData = ();
+
+            fn run(&mut self, _: Self::SystemData) -> Result<(), String> {
+                Err("fail".to_owned())
+            }
+        }
+
+        struct Crash;
+
+        impl<'a> System<'a> for Crash {
+            type SystemData = ();
+
+            fn run(&mut self, _: Self::SystemData) {
+                panic!("crash");
+            }
+        }
+
+        let mut world = World::empty();
+        let mut dispatcher = DispatcherBuilder::new()
+            .with_partitioned(
+                &[1, 2],
+                |partition| {
+                    let builder = DispatcherBuilder::new().with_panic_policy(PanicPolicy::Continue);
+                    match partition {
+                        1 => builder.with_try(Fail, "fail", &[]),
+                        _ => builder.with(Crash, "crash", &[]),
+                    }
+                },
+                "arenas",
+                &[],
+            )
+            .build();
+        dispatcher.setup(&mut world);
+        dispatcher.dispatch(&world);
+
+        let errors = dispatcher.errors();
+        assert_eq!(errors.errors().len(), 1);
+        assert_eq!(errors.errors()[0].name(), "fail");
+        assert_eq!(errors.panics().len(), 1);
+        assert_eq!(errors.panics()[0].name(), "crash");
+    }
+
+    #[test]
+    #[should_panic(expected = "Partitions 1 and 2 of \"arenas\" conflict on the resource")]
+    fn conflicting_partitions() {
+        DispatcherBuilder::new().with_partitioned(
+            &[1, 2],
+            |_| DispatcherBuilder::new().with(Movement::new(1), "movement", &[]),
+            "arenas",
+            &[],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "was accessed without being declared by the `PartitionAccessor`")]
+    fn undeclared_access() {
+        struct Sneaky(PartitionAccessor);
+
+        impl<'a> System<'a> for Sneaky {
+            type SystemData = PartitionData<'a>;
+
+            fn run(&mut self, data: Self::SystemData) {
+                data.fetch_mut::<Speed>().0 += 1;
+            }
+
+            fn accessor<'b>(&'b self) -> AccessorCow<'a, 'b, Self> {
+                AccessorCow::Ref(&self.0)
+            }
+        }
+
+        let world = arenas(&[1]);
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(
+                Sneaky(PartitionAccessor::new(1).read::<Speed>()),
+                "sneaky",
+                &[],
+            )
+            .build();
+
+        dispatcher.dispatch(&world);
+    }
+}
@@ -0,0 +1,263 @@
+use std::{any::Any, time::Duration};
+
+use crate::{
+    dispatch::{BatchController, BatchFetch, BatchWorld},
+    system::{AccessorCow, RunningTime, System},
+    world::{Read, World},
+};
+
+/// The time elapsed since the last dispatch, driving [`FixedTimestep`]
+/// batches.
+///
+/// It has to be updated by the application before each dispatch, e.g. with
+/// the duration of the last frame.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DeltaTime(pub Duration);
+
+/// A [`BatchController`] which dispatches its batch at a fixed simulated
+/// rate, independent of how often the parent dispatcher is dispatched.
+///
+/// The [`DeltaTime`] of every dispatch is accumulated, and the batch is
+/// dispatched once for every full `step` of accumulated time, catching up
+/// with as many dispatches as needed. If more than `max_steps` dispatches are
+/// due, only `max_steps` are run and the rest of the accumulated time is
+/// dropped, so a slow batch can't cause ever growing catch-up work.
+///
+/// To run a single system at a fixed rate, register a batch containing only
+/// that system.
+///
+/// ## Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use shred::{DeltaTime, DispatcherBuilder, FixedTimestep, System, World, Write};
+///
+/// #[derive(Default)]
+/// struct Ticks(u32);
+///
+/// struct Physics;
+///
+/// impl<'a> System<'a> for Physics {
+///     type SystemData = Write<'a, Ticks>;
+///
+///     fn run(&mut self, mut ticks: Self::SystemData) {
+///         ticks.0 += 1;
+///     }
+/// }
+///
+/// let mut dispatcher = DispatcherBuilder::new()
+///     .with_batch(
+///         FixedTimestep::new(Duration::from_millis(10), 5),
+///         DispatcherBuilder::new().with(Physics, "physics", &[]),
+///         "fixed_update",
+///         &[],
+///     )
+///     .build();
+///
+/// let mut world = World::empty();
+/// dispatcher.setup(&mut world);
+///
+/// *world.fetch_mut::<DeltaTime>() = DeltaTime(Duration::from_millis(25));
+/// dispatcher.dispatch(&world);
+/// assert_eq!(world.fetch::<Ticks>().0, 2);
+///
+/// *world.fetch_mut::<DeltaTime>() = DeltaTime(Duration::from_millis(6));
+/// dispatcher.dispatch(&world);
+/// assert_eq!(world.fetch::<Ticks>().0, 3);
+/// ```
+#[derive(Debug)]
+pub struct FixedTimestep {
+    accumulated: Duration,
+    max_steps: u32,
+    step: Duration,
+}
+
+impl FixedTimestep {
+    /// Creates a controller dispatching its batch once per `step`, at most
+    /// `max_steps` times per dispatch of the parent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is zero.
+    pub fn new(step: Duration, max_steps: u32) -> Self {
+        assert!(step > Duration::ZERO, "The step of a `FixedTimestep` can't be zero");
+
+        FixedTimestep {
+            accumulated: Duration::ZERO,
+            max_steps,
+            step,
+        }
+    }
+
+    /// The length of a step.
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// The accumulated time which didn't make up a full step yet.
+    pub fn accumulated(&self) -> Duration {
+        self.accumulated
+    }
+
+    /// Accumulates `delta` and returns the number of steps to run.
+    fn advance(&mut self, delta: Duration) -> u32 {
+        let accumulated = (self.accumulated + delta).as_nanos();
+        let step = self.step.as_nanos();
+
+        self.accumulated = Duration::from_nanos((accumulated % step) as u64);
+
+        (accumulated / step).min(self.max_steps as u128) as u32
+    }
+}
+
+impl<'a, 'b, 'c> BatchController<'a, 'b, 'c> for FixedTimestep {
+    type BatchSystemData = Read<'c, DeltaTime>;
+
+    fn run(
+        &mut self,
+        delta: BatchFetch<'_, Self::BatchSystemData>,
+        mut world: BatchWorld<'_, 'a, 'b, 'c>,
+    ) {
+        let steps = self.advance(delta.0);
+        drop(delta);
+
+        for _ in 0..steps {
+            world.dispatch();
+        }
+    }
+}
+
+/// Runs the system `S` only every `n` dispatches, starting with the first
+/// one.
+///
+/// The system data is still fetched on every dispatch, so the other systems
+/// are scheduled as if `S` ran every time.
+///
+/// ## Examples
+///
+/// ```
+/// use shred::{DispatcherBuilder, Every, System, World, Write};
+///
+/// #[derive(Default)]
+/// struct Saves(u32);
+///
+/// struct Autosave;
+///
+/// impl<'a> System<'a> for Autosave {
+///     type SystemData = Write<'a, Saves>;
+///
+///     fn run(&mut self, mut saves: Self::SystemData) {
+///         saves.0 += 1;
+///     }
+/// }
+///
+/// let mut dispatcher = DispatcherBuilder::new()
+///     .with(Every::new(Autosave, 3), "autosave", &[])
+///     .build();
+///
+/// let mut world = World::empty();
+/// dispatcher.setup(&mut world);
+/// for _ in 0..7 {
+///     dispatcher.dispatch(&world);
+/// }
+///
+/// assert_eq!(world.fetch::<Saves>().0, 3);
+/// ```
+#[derive(Debug)]
+pub struct Every<S> {
+    counter: u32,
+    n: u32,
+    system: S,
+}
+
+impl<S> Every<S> {
+    /// Wraps `system` to run every `n` dispatches.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn new(system: S, n: u32) -> Self {
+        assert!(n > 0, "`Every` can't run a system every 0 dispatches");
+
+        Every {
+            counter: 0,
+            n,
+            system,
+        }
+    }
+
+    /// Returns the wrapped system.
+    pub fn into_inner(self) -> S {
+        self.system
+    }
+}
+
+impl<'a, S> System<'a> for Every<S>
+where
+    S: System<'a>,
+{
+    type SystemData = S::SystemData;
+
+    fn run(&mut self, data: Self::SystemData) {
+        if self.counter == 0 {
+            self.system.run(data);
+        }
+
+        self.counter = (self.counter + 1) % self.n;
+    }
+
+    fn running_time(&self) -> RunningTime {
+        self.system.running_time()
+    }
+
+    fn accessor<'b>(&'b self) -> AccessorCow<'a, 'b, Self> {
+        match self.system.accessor() {
+            AccessorCow::Ref(accessor) => AccessorCow::Ref(accessor),
+            AccessorCow::Owned(accessor) => AccessorCow::Owned(accessor),
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        self.system.setup(world);
+    }
+
+    fn dispose(self, world: &mut World) {
+        self.system.dispose(world);
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        self.system.as_any()
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        self.system.as_any_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::FixedTimestep;
+
+    #[test]
+    fn fixed_timestep_catch_up() {
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10), 3);
+
+        assert_eq!(timestep.advance(Duration::from_millis(25)), 2);
+        assert_eq!(timestep.accumulated(), Duration::from_millis(5));
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(3)), 1);
+        assert_eq!(timestep.accumulated(), Duration::from_millis(2));
+    }
+
+    #[test]
+    fn fixed_timestep_clamp() {
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10), 3);
+
+        assert_eq!(timestep.advance(Duration::from_millis(1007)), 3);
+        assert_eq!(timestep.accumulated(), Duration::from_millis(7));
+        assert_eq!(timestep.advance(Duration::from_millis(3)), 1);
+    }
+}
@@ -1,6 +1,24 @@
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::Mutex,
+};
+
+use ahash::AHashMap as HashMap;
+
 #[cfg(feature = "parallel")]
-use crate::dispatch::dispatcher::ThreadPoolWrapper;
-use crate::{dispatch::stage::Stage, system::RunNow, world::World};
+use crate::dispatch::{dispatcher::ThreadPoolWrapper, util::SharedSlot};
+use crate::{
+    dispatch::{
+        dispatcher::{NamedSystem, SystemExecSend},
+        error::{self, BuildError, DispatchErrors, SystemPanic},
+        graph::{Resolved, SystemGraph, system_name},
+        observer::{CatchPanics, Hooks, Instruments},
+        stage::{Stage, SystemAccess},
+    },
+    system::{RunNow, System},
+    world::World,
+};
 
 /// `Send`able version of [`Dispatcher`](crate::dispatch::Dispatcher).
 ///
@@ -9,11 +27,15 @@ use crate::{dispatch::stage::Stage, system::RunNow, world::World};
 /// Create using [`Dispatcher::try_into_sendable`](crate::dispatch::Dispatcher::try_into_sendable).
 pub struct SendDispatcher<'a> {
     pub(super) stages: Vec<Stage<'a>>,
+    /// Used to place systems inserted after building.
+    pub(super) graph: SystemGraph,
     #[cfg(feature = "parallel")]
-    pub(super) thread_pool: ::std::sync::Arc<::std::sync::RwLock<ThreadPoolWrapper>>,
+    pub(super) thread_pool: SharedSlot<ThreadPoolWrapper>,
+    pub(super) instruments: Instruments,
+    pub(super) errors: DispatchErrors,
 }
 
-impl SendDispatcher<'_> {
+impl<'a> SendDispatcher<'a> {
     /// Sets up all the systems which means they are gonna add default values
     /// for the resources they need.
     pub fn setup(&mut self, world: &mut World) {
@@ -65,17 +87,28 @@ impl SendDispatcher<'_> {
     #[cfg(feature = "parallel")]
     pub fn dispatch_par(&mut self, world: &World) {
         let stages = &mut self.stages;
+        let trace = self.instruments.tracer.begin_frame();
+        let panics = Mutex::default();
+        let hooks = Hooks {
+            observers: &self.instruments.observers,
+            panics: CatchPanics::new(self.instruments.panic_policy, &panics),
+            trace: trace.as_ref(),
+            unchecked_fetch: self.instruments.unchecked_fetch,
+        };
 
         self.thread_pool
-            .read()
-            .unwrap()
-            .as_ref()
+            .read(Clone::clone)
             .unwrap()
             .install(move || {
-                for stage in stages {
-                    stage.execute(world);
-                }
+                hooks.dispatch(|| {
+                    for (index, stage) in stages.iter_mut().enumerate() {
+                        hooks.stage(index, || stage.execute(world, hooks, index));
+                    }
+                })
             });
+
+        self.errors = DispatchErrors::default();
+        self.extend_errors(world, panics);
     }
 
     /// Dispatches the systems (except thread local systems) sequentially.
@@ -86,9 +119,170 @@ impl SendDispatcher<'_> {
     /// Please note that this method assumes that no resource
     /// is currently borrowed. If that's the case, it panics.
     pub fn dispatch_seq(&mut self, world: &World) {
-        for stage in &mut self.stages {
-            stage.execute_seq(world);
+        let stages = &mut self.stages;
+        let trace = self.instruments.tracer.begin_frame();
+        let panics = Mutex::default();
+        let hooks = Hooks {
+            observers: &self.instruments.observers,
+            panics: CatchPanics::new(self.instruments.panic_policy, &panics),
+            trace: trace.as_ref(),
+            unchecked_fetch: self.instruments.unchecked_fetch,
+        };
+
+        hooks.dispatch(|| {
+            for (index, stage) in stages.iter_mut().enumerate() {
+                hooks.stage(index, || stage.execute_seq(world, hooks, index));
+            }
+        });
+
+        self.errors = DispatchErrors::default();
+        self.extend_errors(world, panics);
+    }
+
+    /// Returns the failures of the last dispatch.
+    ///
+    /// Panics are only caught if a [`PanicPolicy`] was configured using
+    /// [`DispatcherBuilder::with_panic_policy`].
+    ///
+    /// [`DispatcherBuilder::with_panic_policy`]: crate::DispatcherBuilder::with_panic_policy
+    pub fn errors(&self) -> &DispatchErrors {
+        &self.errors
+    }
+
+    /// Returns the system registered as `name`, if it is a `T`.
+    ///
+    /// See [`Dispatcher::system`](crate::Dispatcher::system) for details.
+    pub fn system<T>(&self, name: &str) -> Option<&T>
+    where
+        T: Any,
+    {
+        let id = *self.graph.map.get(name)?;
+        let system = self.stages.iter().find_map(|stage| stage.system(id))?;
+
+        system.system.as_any()?.downcast_ref()
+    }
+
+    /// Returns the system registered as `name` mutably, if it is a `T`.
+    ///
+    /// See [`Dispatcher::system`](crate::Dispatcher::system) for details.
+    pub fn system_mut<T>(&mut self, name: &str) -> Option<&mut T>
+    where
+        T: Any,
+    {
+        let id = *self.graph.map.get(name)?;
+        let system = self
+            .stages
+            .iter_mut()
+            .find_map(|stage| stage.system_mut(id))?;
+
+        system.system.as_any_mut()?.downcast_mut()
+    }
+
+    /// Adds a system to the built dispatcher, setting it up with `world`.
+    ///
+    /// The system is placed after the last barrier, like it would have been
+    /// by [`DispatcherBuilder::add`](crate::DispatcherBuilder::add). The
+    /// other systems are placed again, keeping their state.
+    ///
+    /// Fails without changing the dispatcher if `name` is already registered,
+    /// a dependency doesn't exist or the dependencies form a cycle. If the
+    /// `setup` of the system panics, the dispatcher is left unchanged as well.
+    pub fn insert_system<T>(
+        &mut self,
+        world: &mut World,
+        mut system: T,
+        name: &str,
+        dep: &[&str],
+    ) -> Result<(), BuildError>
+    where
+        T: for<'c> System<'c> + Send + 'a,
+    {
+        let id = self.graph.next_id();
+        let display_name = system_name::<T>(name);
+        let access = SystemAccess::of(&system);
+        self.graph.add(id, name, display_name.clone(), access, &[], dep)?;
+
+        let check_from = self.graph.nodes.len() - 1;
+        let order = match self.graph.resolve(check_from) {
+            Ok(order) => order,
+            Err(e) => {
+                self.graph.remove_id(id);
+
+                return Err(e);
+            }
+        };
+
+        // a panicking setup must not leave a node without a system behind
+        let setup = panic::catch_unwind(AssertUnwindSafe(|| system.setup(world)));
+        if let Err(payload) = setup {
+            self.graph.remove_id(id);
+            panic::resume_unwind(payload);
+        }
+        self.relayout(order, Some(NamedSystem::new(id, display_name, Box::new(system))));
+
+        Ok(())
+    }
+
+    /// Removes the system registered as `name` and disposes it, returning
+    /// `false` if there is no such system.
+    ///
+    /// Systems depending on the removed system keep running without that
+    /// dependency until a system with the same name is inserted again.
+    pub fn remove_system(&mut self, world: &mut World, name: &str) -> bool {
+        if self.graph.remove(name).is_none() {
+            return false;
+        }
+
+        let order = self
+            .graph
+            .resolve(self.graph.nodes.len())
+            .expect("Removing a system doesn't add dependencies");
+        for removed in self.relayout(order, None) {
+            removed.system.dispose(world);
         }
+
+        true
+    }
+
+    /// Places all systems into new stages, returning the systems which are
+    /// no longer part of the graph.
+    fn relayout(
+        &mut self,
+        order: Vec<Resolved>,
+        new: Option<NamedSystem<SystemExecSend<'a>>>,
+    ) -> Vec<NamedSystem<SystemExecSend<'a>>> {
+        let mut systems = std::mem::take(&mut self.stages)
+            .into_iter()
+            .flat_map(Stage::into_systems)
+            .chain(new)
+            .map(|system| (system.id, system))
+            .collect::<HashMap<_, _>>();
+
+        self.stages = self.graph.layout(order, |node| systems.remove(&node.id).unwrap());
+
+        systems.into_values().collect()
+    }
+
+    /// Takes the failures of the last dispatch, leaving an empty list.
+    pub fn take_errors(&mut self) -> DispatchErrors {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Records the errors and `panics` of the dispatch and propagates the
+    /// panics if requested.
+    pub(super) fn extend_errors(&mut self, world: &World, panics: Mutex<Vec<SystemPanic>>) {
+        let old = self.errors.panics.len();
+        let mut panics = panics.into_inner().unwrap();
+        if let Some(ref parent) = self.instruments.parent_sink {
+            error::forward(self.instruments.panic_policy, parent, &mut panics);
+        }
+
+        self.errors.panics.extend(panics);
+        for sink in &self.instruments.error_sinks {
+            error::drain_sink(sink, world, &mut self.errors);
+        }
+
+        error::propagate(self.instruments.panic_policy, &self.errors.panics[old..]);
     }
 
     /// This method returns the largest amount of threads this dispatcher
@@ -41,11 +41,12 @@ use smallvec::SmallVec;
 
 use crate::{
     dispatch::{
-        dispatcher::{SystemExecSend, SystemId},
+        dispatcher::{NamedSystem, SystemExecSend, SystemId},
+        observer::Hooks,
         util::check_intersection,
     },
     system::{RunningTime, System},
-    world::{ResourceId, World},
+    world::{ResourceId, World, accumulate},
 };
 
 const MAX_SYSTEMS_PER_GROUP: usize = 5;
@@ -78,10 +79,12 @@ enum InsertionTarget {
 
 #[derive(Default)]
 pub struct Stage<'a> {
-    groups: GroupVec<ArrayVec<SystemExecSend<'a>, MAX_SYSTEMS_PER_GROUP>>,
+    /// The resources the systems accumulate into, merged after the stage.
+    accumulates: Vec<ResourceId>,
+    groups: GroupVec<ArrayVec<NamedSystem<SystemExecSend<'a>>, MAX_SYSTEMS_PER_GROUP>>,
 }
 
-impl Stage<'_> {
+impl<'a> Stage<'a> {
     fn new() -> Self {
         Default::default()
     }
@@ -89,7 +92,7 @@ impl Stage<'_> {
     pub fn setup(&mut self, world: &mut World) {
         for group in &mut self.groups {
             for sys in group {
-                sys.setup(world);
+                sys.system.setup(world);
             }
         }
     }
@@ -97,20 +100,43 @@ impl Stage<'_> {
     pub fn dispose(self, world: &mut World) {
         for group in self.groups {
             for sys in group {
-                sys.dispose(world);
+                sys.system.dispose(world);
             }
         }
     }
 
+    pub fn system(&self, id: SystemId) -> Option<&NamedSystem<SystemExecSend<'a>>> {
+        self.groups.iter().flatten().find(|system| system.id == id)
+    }
+
+    pub fn system_mut(&mut self, id: SystemId) -> Option<&mut NamedSystem<SystemExecSend<'a>>> {
+        self.groups
+            .iter_mut()
+            .flatten()
+            .find(|system| system.id == id)
+    }
+
+    /// Moves the systems out of this stage.
+    pub fn into_systems(self) -> impl Iterator<Item = NamedSystem<SystemExecSend<'a>>> {
+        self.groups.into_iter().flatten()
+    }
+
+    /// Executes the groups of this stage in parallel. If `trace` is given,
+    /// every system run is recorded as a span of stage `index`.
     #[cfg(feature = "parallel")]
-    pub fn execute(&mut self, world: &World) {
+    pub fn execute(&mut self, world: &World, hooks: Hooks, index: usize) {
         use rayon::prelude::*;
 
-        self.groups.par_iter_mut().for_each(|group| {
-            for system in group {
-                system.run_now(world);
-            }
-        });
+        self.groups
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(group_index, group)| {
+                for system in group {
+                    run_system(system, world, hooks, index, group_index);
+                }
+            });
+
+        accumulate::merge(world, self.accumulates.iter().cloned());
     }
 
     /// This function returns the maximum amount of threads this stage
@@ -120,12 +146,61 @@ impl Stage<'_> {
         self.groups.len()
     }
 
-    pub fn execute_seq(&mut self, world: &World) {
-        for group in &mut self.groups {
+    pub fn execute_seq(&mut self, world: &World, hooks: Hooks, index: usize) {
+        for (group_index, group) in self.groups.iter_mut().enumerate() {
             for system in group {
-                system.run_now(world);
+                run_system(system, world, hooks, index, group_index);
             }
         }
+
+        accumulate::merge(world, self.accumulates.iter().cloned());
+    }
+}
+
+fn run_system(
+    system: &mut NamedSystem<SystemExecSend>,
+    world: &World,
+    hooks: Hooks,
+    stage: usize,
+    group: usize,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("system", name = &*system.name, stage, group).entered();
+    #[cfg(not(feature = "tracing"))]
+    let _ = group;
+
+    hooks.system(system, Some(stage), |system| {
+        hooks.run_now(&mut **system, world, true)
+    });
+}
+
+/// What a system accesses and how long it runs, which decides where it is
+/// placed.
+pub struct SystemAccess {
+    pub accumulates: Vec<ResourceId>,
+    pub reads: Vec<ResourceId>,
+    pub running_time: RunningTime,
+    pub writes: Vec<ResourceId>,
+}
+
+impl SystemAccess {
+    pub fn of<'a, T>(system: &T) -> Self
+    where
+        T: System<'a>,
+    {
+        use crate::system::Accessor;
+
+        let accessor = system.accessor();
+
+        let mut accumulates = Vec::new();
+        accessor.extend_accumulates(&mut accumulates);
+
+        SystemAccess {
+            accumulates,
+            reads: accessor.reads(),
+            running_time: system.running_time(),
+            writes: accessor.writes(),
+        }
     }
 }
 
@@ -140,53 +215,55 @@ pub struct StagesBuilder<'a> {
 }
 
 impl<'a> StagesBuilder<'a> {
-    pub fn fetch_all_reads(&self) -> Vec<ResourceId> {
-        let mut v = self
-            .reads
-            .iter()
-            .flatten()
-            .flatten()
-            .cloned()
-            .collect::<Vec<_>>();
-
-        v.sort();
-        v.dedup();
-        v
-    }
-
-    pub fn fetch_all_writes(&self) -> Vec<ResourceId> {
-        let mut v = self
-            .writes
-            .iter()
-            .flatten()
-            .flatten()
-            .cloned()
-            .collect::<Vec<_>>();
-
-        v.sort();
-        v.dedup();
-        v
-    }
-
     pub fn add_barrier(&mut self) {
         self.barrier = self.stages.len();
     }
 
-    pub fn insert<T>(&mut self, mut dep: SmallVec<[SystemId; 4]>, id: SystemId, system: T)
+    #[cfg(test)]
+    pub fn insert<T>(
+        &mut self,
+        dep: SmallVec<[SystemId; 4]>,
+        id: SystemId,
+        name: std::sync::Arc<str>,
+        system: T,
+    )
     where
         T: for<'b> System<'b> + Send + 'a,
     {
-        use crate::system::Accessor;
+        let access = SystemAccess::of(&system);
+
+        self.insert_boxed(dep, &access, NamedSystem::new(id, name, Box::new(system)));
+    }
+
+    pub fn insert_boxed(
+        &mut self,
+        dep: SmallVec<[SystemId; 4]>,
+        access: &SystemAccess,
+        system: NamedSystem<SystemExecSend<'a>>,
+    ) {
+        let (stage, group) = self.place(dep, system.id, access);
 
-        let mut reads = system.accessor().reads();
-        let writes = system.accessor().writes();
+        self.stages[stage].groups[group].push(system);
+    }
+
+    /// Finds the stage and group for a system, without storing the system
+    /// itself.
+    pub fn place(
+        &mut self,
+        mut dep: SmallVec<[SystemId; 4]>,
+        id: SystemId,
+        access: &SystemAccess,
+    ) -> (usize, usize) {
+        let mut reads = access.reads.clone();
+        let writes = access.writes.clone();
 
         reads.sort();
         reads.dedup();
 
-        let new_time = system.running_time();
+        let new_time = access.running_time;
 
-        let target = self.insertion_target(&reads, &writes, &mut dep, new_time);
+        let target =
+            self.insertion_target(&reads, &writes, &access.accumulates, &mut dep, new_time);
 
         let (stage, group) = match target {
             InsertionTarget::Stage(stage) => {
@@ -209,8 +286,14 @@ impl<'a> StagesBuilder<'a> {
         self.ids[stage][group].push(id);
         self.reads[stage][group].extend(reads);
         self.running_time[stage][group] += new_time as u8;
-        self.stages[stage].groups[group].push(Box::new(system));
         self.writes[stage][group].extend(writes);
+
+        let accumulates = &mut self.stages[stage].accumulates;
+        accumulates.extend(access.accumulates.iter().cloned());
+        accumulates.sort();
+        accumulates.dedup();
+
+        (stage, group)
     }
 
     pub fn build(self) -> Vec<Stage<'a>> {
@@ -220,13 +303,8 @@ impl<'a> StagesBuilder<'a> {
     pub fn write_par_seq(
         &self,
         f: &mut fmt::Formatter,
-        map: &HashMap<String, SystemId>,
+        map: &HashMap<SystemId, &str>,
     ) -> fmt::Result {
-        let map: HashMap<_, _> = map
-            .iter()
-            .map(|(key, value)| (*value, key as &str))
-            .collect();
-
         writeln!(f, "seq![")?;
         for stage in &self.ids {
             writeln!(f, "\tpar![")?;
@@ -267,6 +345,7 @@ impl<'a> StagesBuilder<'a> {
         &self,
         new_reads: R,
         new_writes: W,
+        new_accumulates: &[ResourceId],
         new_dep: &mut SmallVec<[SystemId; 4]>,
         new_time: RunningTime,
     ) -> InsertionTarget
@@ -290,6 +369,15 @@ impl<'a> StagesBuilder<'a> {
                     new_writes.clone(),
                     new_dep,
                 );
+                let conflict = if self.accumulation_conflict(
+                    stage,
+                    new_reads.clone().chain(new_writes.clone()),
+                    new_accumulates,
+                ) {
+                    Conflict::Multiple
+                } else {
+                    conflict
+                };
                 self.remove_ids(stage, new_dep);
                 (stage, conflict)
             })
@@ -309,6 +397,30 @@ impl<'a> StagesBuilder<'a> {
             .unwrap_or(InsertionTarget::NewStage)
     }
 
+    /// Returns `true` if a system can't be placed into `stage` because it
+    /// accumulates into a resource accessed by the stage or accesses a
+    /// resource the stage accumulates into.
+    ///
+    /// Grouping doesn't resolve these conflicts, since the accumulated values
+    /// are only merged after the whole stage.
+    fn accumulation_conflict<'rw, A>(
+        &self,
+        stage: usize,
+        new_accesses: A,
+        new_accumulates: &[ResourceId],
+    ) -> bool
+    where
+        A: Iterator<Item = &'rw ResourceId>,
+    {
+        let accesses = self.reads[stage]
+            .iter()
+            .flatten()
+            .chain(self.writes[stage].iter().flatten());
+
+        check_intersection(new_accesses, self.stages[stage].accumulates.iter())
+            || check_intersection(new_accumulates.iter(), accesses)
+    }
+
     fn improves_balance(&self, stage: usize, group: usize, new_time: u8) -> bool {
         let max = *self.running_time[stage].iter().max().unwrap() as i8;
         let old_time = self.running_time[stage][group];
@@ -541,9 +653,9 @@ mod tests {
 
         let mut builder: StagesBuilder = Default::default();
 
-        builder.insert(SmallVec::new(), SystemId(0), SysA);
-        builder.insert(SmallVec::new(), SystemId(1), SysB);
-        builder.insert(SmallVec::new(), SystemId(2), SysC);
+        builder.insert(SmallVec::new(), SystemId(0), "a".into(), SysA);
+        builder.insert(SmallVec::new(), SystemId(1), "b".into(), SysB);
+        builder.insert(SmallVec::new(), SystemId(2), "c".into(), SysC);
 
         let ids = &builder.ids[0];
 
@@ -564,9 +676,19 @@ mod tests {
             fn run(&mut self, _: Self::SystemData) {}
         }
 
-        builder.insert(SmallVec::from(&[][..]), SystemId(0), Sys);
-        builder.insert(SmallVec::from(&[SystemId(0)][..]), SystemId(1), Sys);
-        builder.insert(SmallVec::from(&[SystemId(1)][..]), SystemId(2), Sys);
+        builder.insert(SmallVec::from(&[][..]), SystemId(0), "0".into(), Sys);
+        builder.insert(
+            SmallVec::from(&[SystemId(0)][..]),
+            SystemId(1),
+            "1".into(),
+            Sys,
+        );
+        builder.insert(
+            SmallVec::from(&[SystemId(1)][..]),
+            SystemId(2),
+            "2".into(),
+            Sys,
+        );
 
         assert_eq!(builder.ids[0][0][0], SystemId(0));
         assert_eq!(builder.ids[1][0][0], SystemId(1));
@@ -0,0 +1,342 @@
+//! Recording of dispatches in the Chrome trace event format.
+//!
+//! The produced JSON can be loaded into `chrome://tracing` or
+//! [Perfetto](https://ui.perfetto.dev) for offline performance analysis.
+
+use std::{
+    borrow::Cow,
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::dispatch::util::SharedSlot;
+
+/// Shared slot for a [`TraceRecorder`] and the current frame, handed down to
+/// batch dispatchers the same way the thread pool is.
+pub type TraceRecorderWrapper = SharedSlot<TraceState>;
+
+/// Records a span for every system run and every dispatch and writes them out
+/// as Chrome trace-event JSON.
+///
+/// Attach it using
+/// [`DispatcherBuilder::with_trace_recorder`](crate::DispatcherBuilder::with_trace_recorder).
+/// Batches added to the builder share the recorder of their parent, so the
+/// spans of sub-dispatches (e.g. the iterations of a [`MultiDispatcher`]) end
+/// up in the same trace.
+///
+/// Every span carries the stage index and the dispatch frame as arguments;
+/// the trace thread id is the index of the rayon worker that executed the
+/// system (`0` is used for the thread calling `dispatch`). Spans of batches
+/// are tagged with the frame of the parent dispatch.
+///
+/// ## Examples
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use shred::{DispatcherBuilder, System, TraceRecorder, World};
+/// # struct Sys;
+/// # impl<'a> System<'a> for Sys {
+/// #     type SystemData = ();
+/// #     fn run(&mut self, _: ()) {}
+/// # }
+/// let recorder = Arc::new(TraceRecorder::new());
+/// let mut dispatcher = DispatcherBuilder::new()
+///     .with(Sys, "sys", &[])
+///     .with_trace_recorder(recorder.clone())
+///     .build();
+///
+/// let world = World::empty();
+/// dispatcher.dispatch(&world);
+///
+/// let mut json = Vec::new();
+/// recorder.write_json(&mut json).unwrap();
+/// ```
+///
+/// [`MultiDispatcher`]: crate::MultiDispatcher
+pub struct TraceRecorder {
+    events: Mutex<Vec<TraceEvent>>,
+    start: Instant,
+}
+
+impl TraceRecorder {
+    /// Creates a new, empty recorder. Timestamps are relative to the creation
+    /// of the recorder.
+    pub fn new() -> Self {
+        TraceRecorder {
+            events: Mutex::new(Vec::new()),
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns the number of recorded spans.
+    pub fn len(&self) -> usize {
+        self.events.lock().unwrap().len()
+    }
+
+    /// Returns `true` if no span has been recorded (yet).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all the spans recorded so far.
+    pub fn clear(&self) {
+        self.events.lock().unwrap().clear();
+    }
+
+    /// Writes all recorded spans as a Chrome trace-event JSON object.
+    pub fn write_json<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        let events = self.events.lock().unwrap();
+
+        let mut tids = events.iter().map(|e| e.tid).collect::<Vec<_>>();
+        tids.sort_unstable();
+        tids.dedup();
+
+        writeln!(writer, "{{\"traceEvents\":[")?;
+        let mut first = true;
+        for tid in tids {
+            let name: Cow<str> = match tid {
+                0 => "main".into(),
+                n => format!("worker {}", n - 1).into(),
+            };
+
+            separate(&mut writer, &mut first)?;
+            write!(
+                writer,
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+                tid, name
+            )?;
+        }
+        for event in events.iter() {
+            separate(&mut writer, &mut first)?;
+            write!(
+                writer,
+                "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":0,\"tid\":{},\"args\":{{\"frame\":{}",
+                Escaped(&event.name),
+                event.cat,
+                micros(event.ts),
+                micros(event.dur),
+                event.tid,
+                event.frame
+            )?;
+            if let Some(stage) = event.stage {
+                write!(writer, ",\"stage\":{}", stage)?;
+            }
+            write!(writer, "}}}}")?;
+        }
+        writeln!(writer, "\n]}}")
+    }
+
+    fn record<R>(
+        &self,
+        name: &Arc<str>,
+        cat: &'static str,
+        stage: Option<usize>,
+        frame: u64,
+        f: impl FnOnce() -> R,
+    ) -> R {
+        let begin = Instant::now();
+        let r = f();
+        let end = Instant::now();
+
+        self.events.lock().unwrap().push(TraceEvent {
+            cat,
+            dur: end - begin,
+            frame,
+            name: name.clone(),
+            stage,
+            tid: current_thread(),
+            ts: begin - self.start,
+        });
+
+        r
+    }
+}
+
+impl Default for TraceRecorder {
+    fn default() -> Self {
+        TraceRecorder::new()
+    }
+}
+
+struct TraceEvent {
+    cat: &'static str,
+    dur: Duration,
+    frame: u64,
+    name: Arc<str>,
+    stage: Option<usize>,
+    tid: usize,
+    ts: Duration,
+}
+
+/// The recorder of a dispatcher and its batches.
+#[derive(Default)]
+pub struct TraceState {
+    pub recorder: Option<Arc<TraceRecorder>>,
+    /// The current frame of the dispatcher, which its batches don't advance.
+    frame: u64,
+}
+
+/// The tracing state of a single dispatcher.
+#[derive(Default)]
+pub struct Tracer {
+    pub(crate) state: TraceRecorderWrapper,
+    /// Name of the span covering a whole dispatch; the batch name for
+    /// sub-dispatchers.
+    pub(crate) label: Option<Arc<str>>,
+}
+
+impl Tracer {
+    /// Creates a tracer recording into whatever `state` holds.
+    pub fn new(state: TraceRecorderWrapper) -> Self {
+        Tracer { state, label: None }
+    }
+
+    /// Begins a new frame and returns the context to trace it with, if a
+    /// recorder is attached.
+    ///
+    /// Batches stay in the frame of their parent dispatch.
+    pub fn begin_frame(&mut self) -> Option<TraceFrame> {
+        if self.label.is_some() {
+            return self.current_frame();
+        }
+
+        let (recorder, frame) = self.state.write(|state| {
+            state.frame += 1;
+
+            Some((state.recorder.clone()?, state.frame))
+        })?;
+
+        Some(self.frame_with(recorder, frame))
+    }
+
+    /// Returns the context of the current frame, without starting a new one.
+    pub fn current_frame(&self) -> Option<TraceFrame> {
+        let (recorder, frame) = self
+            .state
+            .read(|state| Some((state.recorder.clone()?, state.frame)))?;
+
+        Some(self.frame_with(recorder, frame))
+    }
+
+    fn frame_with(&self, recorder: Arc<TraceRecorder>, frame: u64) -> TraceFrame {
+        let (label, cat) = match self.label {
+            Some(ref label) => (label.clone(), "batch"),
+            None => ("dispatch".into(), "dispatch"),
+        };
+
+        TraceFrame {
+            cat,
+            frame,
+            label,
+            recorder,
+        }
+    }
+}
+
+/// Context for tracing the systems of one dispatch.
+pub struct TraceFrame {
+    cat: &'static str,
+    frame: u64,
+    label: Arc<str>,
+    recorder: Arc<TraceRecorder>,
+}
+
+impl TraceFrame {
+    /// Records `f` as the span of the whole dispatch.
+    pub fn dispatch<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.recorder
+            .record(&self.label, self.cat, None, self.frame, f)
+    }
+
+    /// Records `f` as the span of a system run in `stage`.
+    pub fn system<R>(&self, name: &Arc<str>, stage: usize, f: impl FnOnce() -> R) -> R {
+        self.recorder
+            .record(name, "system", Some(stage), self.frame, f)
+    }
+
+    /// Records `f` as the span of a thread local system run.
+    pub fn thread_local<R>(&self, name: &Arc<str>, f: impl FnOnce() -> R) -> R {
+        self.recorder
+            .record(name, "thread_local", None, self.frame, f)
+    }
+}
+
+fn current_thread() -> usize {
+    #[cfg(feature = "parallel")]
+    {
+        rayon::current_thread_index().map_or(0, |index| index + 1)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        0
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_nanos() as f64 / 1000.0
+}
+
+fn separate<W: io::Write>(writer: &mut W, first: &mut bool) -> io::Result<()> {
+    if *first {
+        *first = false;
+
+        Ok(())
+    } else {
+        writeln!(writer, ",")
+    }
+}
+
+/// Escapes a string for use inside a JSON string literal.
+struct Escaped<'a>(&'a str);
+
+impl std::fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use std::fmt::Write;
+
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_names() {
+        assert_eq!(
+            Escaped("a \"quoted\"\\name\n").to_string(),
+            "a \\\"quoted\\\"\\\\name\\u000a"
+        );
+    }
+
+    #[test]
+    fn writes_spans() {
+        let recorder = TraceRecorder::new();
+        let name: Arc<str> = "sys".into();
+
+        recorder.record(&name, "system", Some(2), 7, || ());
+        assert_eq!(recorder.len(), 1);
+
+        let mut json = Vec::new();
+        recorder.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+
+        assert!(json.starts_with("{\"traceEvents\":["));
+        assert!(json.contains("\"name\":\"sys\",\"cat\":\"system\",\"ph\":\"X\""));
+        assert!(json.contains("\"args\":{\"frame\":7,\"stage\":2}"));
+
+        recorder.clear();
+        assert!(recorder.is_empty());
+    }
+}
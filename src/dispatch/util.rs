@@ -1,3 +1,13 @@
+use std::{
+    any::Any,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    system::{AccessorCow, RunningTime, System},
+    world::World,
+};
+
 pub fn check_intersection<'i, 'j, T, I, J>(mut i: I, j: J) -> bool
 where
     I: Iterator<Item = &'i T>,
@@ -6,3 +16,91 @@ where
 {
     i.any(|elem_i| j.clone().any(|elem_j| *elem_j == *elem_i))
 }
+
+/// A value shared by a dispatcher builder and the batches built from it.
+///
+/// When a builder is merged into another one, its slot is linked to the slot
+/// of the other builder, so batches built from either see the same value.
+pub struct SharedSlot<T>(Arc<RwLock<Link<T>>>);
+
+enum Link<T> {
+    Value(T),
+    To(SharedSlot<T>),
+}
+
+impl<T> SharedSlot<T> {
+    /// Calls `f` with the value of the slot this one is linked to.
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        match *self.0.read().unwrap() {
+            Link::Value(ref value) => f(value),
+            Link::To(ref target) => target.read(f),
+        }
+    }
+
+    /// Calls `f` with the value of the slot this one is linked to.
+    pub fn write<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        match *self.0.write().unwrap() {
+            Link::Value(ref mut value) => f(value),
+            Link::To(ref target) => target.write(f),
+        }
+    }
+
+    /// Makes this slot share the value of `target`, dropping its own.
+    pub fn link(&self, target: &SharedSlot<T>) {
+        *self.0.write().unwrap() = Link::To(target.clone());
+    }
+}
+
+impl<T> Clone for SharedSlot<T> {
+    fn clone(&self) -> Self {
+        SharedSlot(self.0.clone())
+    }
+}
+
+impl<T: Default> Default for SharedSlot<T> {
+    fn default() -> Self {
+        SharedSlot(Arc::new(RwLock::new(Link::Value(T::default()))))
+    }
+}
+
+/// Returns the wrapped `'static` system from `as_any`, so it can be accessed
+/// from the dispatcher without implementing `as_any` itself.
+pub struct Accessible<S>(pub S);
+
+impl<'a, S> System<'a> for Accessible<S>
+where
+    S: System<'a> + 'static,
+{
+    type SystemData = S::SystemData;
+
+    fn run(&mut self, data: Self::SystemData) {
+        self.0.run(data);
+    }
+
+    fn running_time(&self) -> RunningTime {
+        self.0.running_time()
+    }
+
+    fn accessor<'b>(&'b self) -> AccessorCow<'a, 'b, Self> {
+        match self.0.accessor() {
+            AccessorCow::Ref(accessor) => AccessorCow::Ref(accessor),
+            AccessorCow::Owned(accessor) => AccessorCow::Owned(accessor),
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        self.0.setup(world);
+    }
+
+    fn dispose(self, world: &mut World) {
+        self.0.dispose(world);
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(&self.0)
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        Some(&mut self.0)
+    }
+}
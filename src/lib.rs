@@ -108,17 +108,24 @@ pub use crate::dispatch::AsyncDispatcher;
 pub use crate::dispatch::{Par, ParSeq, RunWithPool, Seq};
 pub use crate::{
     dispatch::{
-        BatchAccessor, BatchController, BatchUncheckedWorld, Dispatcher, DispatcherBuilder,
-        MultiDispatchController, MultiDispatcher, SendDispatcher,
+        BatchAccessor, BatchController, BatchFetch, BatchUncheckedWorld, BatchWorld, BuildError,
+        Converge, ConvergeController, Convergence, DeltaTime, DispatchErrors, DispatchObserver,
+        Dispatcher, DispatcherBuilder, ErrorHandler, Every, FixedTimestep, MultiDispatchController,
+        MultiDispatcher, PanicPolicy, PartitionAccessor, PartitionData, SendDispatcher, SystemError,
+        SystemId, SystemPanic, TraceRecorder,
+    },
+    meta::{
+        CastFrom, MetaIter, MetaIterMut, MetaIterWithIds, MetaTable, ReadAll, ReadAllAccessor,
+        WriteAll, WriteAllAccessor,
     },
-    meta::{CastFrom, MetaIter, MetaIterMut, MetaTable},
     system::{
         Accessor, AccessorCow, DynamicSystemData, RunNow, RunningTime, StaticAccessor, System,
-        SystemData,
+        SystemData, TrySystem,
     },
     world::{
-        DefaultProvider, Entry, Fetch, FetchMut, PanicHandler, Read, ReadExpect, Resource,
-        ResourceId, SetupHandler, World, Write, WriteExpect,
+        Accumulate, Concurrent, ConcurrentResource, DefaultProvider, Entry, Fetch, FetchMut, Merge,
+        PanicHandler, Read, ReadExpect, Resource, ResourceId, ResourceSlot, SetupHandler, World,
+        Write, WriteExpect,
     },
 };
 
@@ -1,9 +1,9 @@
-use std::{any::TypeId, collections::hash_map::Entry, marker::PhantomData};
+use std::{any::TypeId, collections::hash_map::Entry, marker::PhantomData, sync::Arc};
 
 use ahash::AHashMap as HashMap;
 
 use crate::cell::{AtomicRef, AtomicRefMut};
-use crate::{Resource, ResourceId, World};
+use crate::{Accessor, DynamicSystemData, Fetch, Resource, ResourceId, World};
 
 #[cfg(feature = "nightly")]
 use core::ptr::{DynMetadata, Pointee};
@@ -17,10 +17,31 @@ unsafe impl<T> Send for Invariant<T> where T: ?Sized {}
 
 unsafe impl<T> Sync for Invariant<T> where T: ?Sized {}
 
+/// Returns `true` if `id` isn't one of the `declared` ids, which are sorted.
+/// Missing implementors are skipped, present ones can't be accessed safely.
+///
+/// # Panics
+///
+/// Panics if the undeclared resource is in `world`.
+fn is_undeclared(declared: Option<&[ResourceId]>, id: &ResourceId, world: &World) -> bool {
+    let undeclared = declared.map_or(false, |ids| ids.binary_search(id).is_err());
+
+    // SAFETY: The resource is only checked for presence.
+    if undeclared && unsafe { world.try_fetch_internal(id.clone()) }.is_some() {
+        panic!(
+            "Implementor {:?} was registered after creating the accessor, which doesn't declare it",
+            id
+        );
+    }
+
+    undeclared
+}
+
 /// Helper trait for the `MetaTable`.
 ///
 /// This trait is required to be implemented for a trait to be compatible with
-/// the meta table.
+/// the meta table. Prefer implementing it using the [`cast_from!`] macro,
+/// which doesn't require any `unsafe` code.
 ///
 /// # Safety
 ///
@@ -51,6 +72,56 @@ pub unsafe trait CastFrom<T> {
     fn cast(t: *mut T) -> *mut Self;
 }
 
+/// Implements [`CastFrom`] for a trait object type, so that it can be used
+/// with the `MetaTable`.
+///
+/// `cast_from!(dyn Trait)` implements `CastFrom<T>` for `dyn Trait` and every
+/// `T: Trait + 'static`. Additional bounds of the trait object are supported,
+/// e.g. `cast_from!(dyn Trait + Send + Sync)`.
+///
+/// # Examples
+///
+/// ```
+/// use shred::{MetaTable, World, cast_from};
+///
+/// trait Named {
+///     fn name(&self) -> &str;
+/// }
+///
+/// cast_from!(dyn Named);
+///
+/// struct Player;
+///
+/// impl Named for Player {
+///     fn name(&self) -> &str {
+///         "player"
+///     }
+/// }
+///
+/// let mut world = World::empty();
+/// world.insert(Player);
+///
+/// let mut table = MetaTable::<dyn Named>::new();
+/// table.register::<Player>();
+///
+/// assert_eq!(table.iter(&world).next().unwrap().name(), "player");
+/// ```
+#[macro_export]
+macro_rules! cast_from {
+    (dyn $($bound:tt)+) => {
+        // SAFETY: the pointer is only unsized, which keeps its address and
+        // provenance and attaches the vtable of `__T`.
+        unsafe impl<__T> $crate::CastFrom<__T> for dyn $($bound)+
+        where
+            __T: $($bound)+ + 'static,
+        {
+            fn cast(t: *mut __T) -> *mut Self {
+                t
+            }
+        }
+    };
+}
+
 /// An iterator for the `MetaTable`.
 pub struct MetaIter<'a, T: ?Sized + 'a> {
     #[cfg(not(feature = "nightly"))]
@@ -58,7 +129,10 @@ pub struct MetaIter<'a, T: ?Sized + 'a> {
     #[cfg(feature = "nightly")]
     vtables: &'a [DynMetadata<T>],
     index: usize,
-    tys: &'a [TypeId],
+    /// The registered ids and the index of the vtable of their type.
+    ids: &'a [(ResourceId, usize)],
+    /// Only these ids are yielded, if set.
+    declared: Option<&'a [ResourceId]>,
     // `MetaIter` is invariant over `T`
     marker: PhantomData<Invariant<T>>,
     world: &'a World,
@@ -74,24 +148,26 @@ where
     #[allow(clippy::borrowed_box)] // variant of https://github.com/rust-lang/rust-clippy/issues/5770
     fn next(&mut self) -> Option<<Self as Iterator>::Item> {
         loop {
-            let resource_id = match self.tys.get(self.index) {
-                Some(&x) => ResourceId::from_type_id(x),
+            let (resource_id, index) = match self.ids.get(self.index) {
+                Some((id, index)) => (id.clone(), *index),
                 None => return None,
             };
-
-            let index = self.index;
             self.index += 1;
 
+            if is_undeclared(self.declared, &resource_id, self.world) {
+                continue;
+            }
+
             // SAFETY: We just read the value and don't replace it.
             if let Some(res) = unsafe { self.world.try_fetch_internal(resource_id) } {
                 let vtable_fn = self.vtable_fns[index];
                 let trait_object = AtomicRef::map(res.borrow(), |res: &Box<dyn Resource>| {
                     let ptr: *const dyn Resource = Box::as_ref(res);
                     let trait_ptr = (vtable_fn)(ptr.cast::<()>().cast_mut());
-                    // SAFETY: For a particular index we store a corresponding
-                    // TypeId and vtable_fn in tys and vtable_fns respectively.
-                    // We rely on `try_fetch_interal` returning a trait object
-                    // with a concrete type that has the provided TypeId. The
+                    // SAFETY: Every id in `ids` is stored with the index of
+                    // the vtable_fn of its type in vtable_fns. We rely on
+                    // `try_fetch_interal` returning a trait object with a
+                    // concrete type that has the TypeId of the id. The
                     // signature of the closure parameter of `AtomicRef::map`
                     // should ensure we aren't accidentally extending the
                     // lifetime here. Also see safety note in `MetaTable::get`.
@@ -115,24 +191,26 @@ where
     #[allow(clippy::borrowed_box)] // variant of https://github.com/rust-lang/rust-clippy/issues/5770
     fn next(&mut self) -> Option<<Self as Iterator>::Item> {
         loop {
-            let resource_id = match self.tys.get(self.index) {
-                Some(&x) => ResourceId::from_type_id(x),
+            let (resource_id, index) = match self.ids.get(self.index) {
+                Some((id, index)) => (id.clone(), *index),
                 None => return None,
             };
-
-            let index = self.index;
             self.index += 1;
 
+            if is_undeclared(self.declared, &resource_id, self.world) {
+                continue;
+            }
+
             // SAFETY: We just read the value and don't replace it.
             if let Some(res) = unsafe { self.world.try_fetch_internal(resource_id) } {
                 let vtable = self.vtables[index];
                 let trait_object = AtomicRef::map(res.borrow(), |res: &Box<dyn Resource>| {
                     let ptr: *const dyn Resource = Box::as_ref(res);
                     let trait_ptr = core::ptr::from_raw_parts(ptr.cast::<()>(), vtable);
-                    // SAFETY: For a particular index we store a corresponding
-                    // TypeId and vtable in tys and vtables respectively.
-                    // We rely on `try_fetch_interal` returning a trait object
-                    // with a concrete type that has the provided TypeId. The
+                    // SAFETY: Every id in `ids` is stored with the index of
+                    // the vtable of its type in vtables. We rely on
+                    // `try_fetch_interal` returning a trait object with a
+                    // concrete type that has the TypeId of the id. The
                     // signature of the closure parameter of `AtomicRef::map`
                     // should ensure we aren't accidentally extending the
                     // lifetime here. Also see safety note in `MetaTable::get`.
@@ -145,6 +223,25 @@ where
     }
 }
 
+/// An iterator for the `MetaTable` which also yields the id of each resource.
+pub struct MetaIterWithIds<'a, T: ?Sized + 'a>(MetaIter<'a, T>);
+
+impl<'a, T> Iterator for MetaIterWithIds<'a, T>
+where
+    T: ?Sized + 'a,
+    MetaIter<'a, T>: Iterator<Item = AtomicRef<'a, T>>,
+{
+    type Item = (ResourceId, AtomicRef<'a, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let object = self.0.next()?;
+        // `MetaIter` advanced past the id it just yielded
+        let (id, _) = &self.0.ids[self.0.index - 1];
+
+        Some((id.clone(), object))
+    }
+}
+
 /// A mutable iterator for the `MetaTable`.
 pub struct MetaIterMut<'a, T: ?Sized + 'a> {
     #[cfg(not(feature = "nightly"))]
@@ -152,7 +249,10 @@ pub struct MetaIterMut<'a, T: ?Sized + 'a> {
     #[cfg(feature = "nightly")]
     vtables: &'a [DynMetadata<T>],
     index: usize,
-    tys: &'a [TypeId],
+    /// The registered ids and the index of the vtable of their type.
+    ids: &'a [(ResourceId, usize)],
+    /// Only these ids are yielded, if set.
+    declared: Option<&'a [ResourceId]>,
     // `MetaIterMut` is invariant over `T`
     marker: PhantomData<Invariant<T>>,
     world: &'a World,
@@ -167,14 +267,16 @@ where
 
     fn next(&mut self) -> Option<<Self as Iterator>::Item> {
         loop {
-            let resource_id = match self.tys.get(self.index) {
-                Some(&x) => ResourceId::from_type_id(x),
+            let (resource_id, index) = match self.ids.get(self.index) {
+                Some((id, index)) => (id.clone(), *index),
                 None => return None,
             };
-
-            let index = self.index;
             self.index += 1;
 
+            if is_undeclared(self.declared, &resource_id, self.world) {
+                continue;
+            }
+
             // Note: this relies on implementation details of
             // try_fetch_internal!
             // SAFETY: We don't swap out the Box or expose a mutable reference to it.
@@ -184,10 +286,10 @@ where
                     AtomicRefMut::map(res.borrow_mut(), |res: &mut Box<dyn Resource>| {
                         let ptr: *mut dyn Resource = Box::as_mut(res);
                         let trait_ptr = (vtable_fn)(ptr.cast::<()>());
-                        // SAFETY: For a particular index we store a corresponding
-                        // TypeId and vtable_fn in tys and vtable_fns respectively.
-                        // We rely on `try_fetch_interal` returning a trait object
-                        // with a concrete type that has the provided TypeId. The
+                        // SAFETY: Every id in `ids` is stored with the index of
+                        // the vtable_fn of its type in vtable_fns. We rely on
+                        // `try_fetch_interal` returning a trait object with a
+                        // concrete type that has the TypeId of the id. The
                         // signature of the closure parameter of `AtomicRefMut::map`
                         // should ensure we aren't accidentally extending the
                         // lifetime here. Also see safety note in
@@ -211,14 +313,16 @@ where
 
     fn next(&mut self) -> Option<<Self as Iterator>::Item> {
         loop {
-            let resource_id = match self.tys.get(self.index) {
-                Some(&x) => ResourceId::from_type_id(x),
+            let (resource_id, index) = match self.ids.get(self.index) {
+                Some((id, index)) => (id.clone(), *index),
                 None => return None,
             };
-
-            let index = self.index;
             self.index += 1;
 
+            if is_undeclared(self.declared, &resource_id, self.world) {
+                continue;
+            }
+
             // Note: this relies on implementation details of
             // try_fetch_internal!
             // SAFETY: We don't swap out the Box or expose a mutable reference to it.
@@ -228,10 +332,10 @@ where
                     AtomicRefMut::map(res.borrow_mut(), |res: &mut Box<dyn Resource>| {
                         let ptr: *mut dyn Resource = Box::as_mut(res);
                         let trait_ptr = core::ptr::from_raw_parts_mut(ptr.cast::<()>(), vtable);
-                        // SAFETY: For a particular index we store a corresponding
-                        // TypeId and vtable in tys and vtables respectively.
-                        // We rely on `try_fetch_interal` returning a trait object
-                        // with a concrete type that has the provided TypeId. The
+                        // SAFETY: Every id in `ids` is stored with the index of
+                        // the vtable of its type in vtables. We rely on
+                        // `try_fetch_interal` returning a trait object with a
+                        // concrete type that has the TypeId of the id. The
                         // signature of the closure parameter of `AtomicRefMut::map`
                         // should ensure we aren't accidentally extending the
                         // lifetime here. Also see safety note in
@@ -282,10 +386,14 @@ where
 /// `MetaTable::register`. Later on, you can iterate over all resources that
 /// implement `Foo` without knowing their specific type.
 ///
+/// Resources inserted with a dynamic id are registered using
+/// `MetaTable::register_id`, or `MetaTable::register_all` for every resource
+/// of a type.
+///
 /// # Examples
 ///
 /// ```
-/// use shred::{CastFrom, MetaTable, World};
+/// use shred::{MetaTable, World, cast_from};
 ///
 /// trait Object {
 ///     fn method1(&self) -> i32;
@@ -293,14 +401,7 @@ where
 ///     fn method2(&mut self, x: i32);
 /// }
 ///
-/// unsafe impl<T> CastFrom<T> for dyn Object
-/// where
-///     T: Object + 'static,
-/// {
-///     fn cast(t: *mut T) -> *mut Self {
-///         t
-///     }
-/// }
+/// cast_from!(dyn Object);
 ///
 /// struct ImplementorA(i32);
 ///
@@ -347,7 +448,10 @@ pub struct MetaTable<T: ?Sized> {
     #[cfg(feature = "nightly")]
     vtables: Vec<DynMetadata<T>>,
     indices: HashMap<TypeId, usize>,
-    tys: Vec<TypeId>,
+    /// The registered ids and the index of the vtable of their type.
+    ids: Vec<(ResourceId, usize)>,
+    /// The registered types and their names, in the order of their vtables.
+    types: Vec<(TypeId, &'static str)>,
     // `MetaTable` is invariant over `T`
     marker: PhantomData<Invariant<T>>,
 }
@@ -369,35 +473,59 @@ impl<T: ?Sized> MetaTable<T> {
         R: Resource,
         T: CastFrom<R> + 'static,
     {
-        let ty_id = TypeId::of::<R>();
-        let vtable_fn = attach_vtable::<T, R>;
+        self.register_id::<R>(ResourceId::new::<R>());
+    }
 
-        // Important: ensure no entry exists twice!
-        let len = self.indices.len();
-        match self.indices.entry(ty_id) {
-            Entry::Occupied(occ) => {
-                let ind = *occ.get();
+    /// Registers a resource `R` that implements the trait `T`.
+    #[cfg(feature = "nightly")]
+    pub fn register<R>(&mut self)
+    where
+        R: Resource,
+        T: CastFrom<R> + 'static,
+        T: Pointee<Metadata = DynMetadata<T>>,
+    {
+        self.register_id::<R>(ResourceId::new::<R>());
+    }
 
-                self.vtable_fns[ind] = vtable_fn;
-            }
-            Entry::Vacant(vac) => {
-                vac.insert(len);
+    /// Registers the resource `R` stored under `id`, which may have a dynamic
+    /// id (see [`ResourceId::new_with_dynamic_id`]).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` isn't the id of an `R`.
+    #[cfg(not(feature = "nightly"))]
+    pub fn register_id<R>(&mut self, id: ResourceId)
+    where
+        R: Resource,
+        T: CastFrom<R> + 'static,
+    {
+        id.assert_same_type_id::<R>();
 
-                self.vtable_fns.push(vtable_fn);
-                self.tys.push(ty_id);
-            }
-        }
+        let vtable_fn = attach_vtable::<T, R>;
+        let index = insert_vtable(
+            &mut self.indices,
+            &mut self.vtable_fns,
+            TypeId::of::<R>(),
+            vtable_fn,
+        );
+        self.insert_id::<R>(id, index);
     }
 
-    /// Registers a resource `R` that implements the trait `T`.
+    /// Registers the resource `R` stored under `id`, which may have a dynamic
+    /// id (see [`ResourceId::new_with_dynamic_id`]).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` isn't the id of an `R`.
     #[cfg(feature = "nightly")]
-    pub fn register<R>(&mut self)
+    pub fn register_id<R>(&mut self, id: ResourceId)
     where
         R: Resource,
         T: CastFrom<R> + 'static,
         T: Pointee<Metadata = DynMetadata<T>>,
     {
-        let ty_id = TypeId::of::<R>();
+        id.assert_same_type_id::<R>();
+
         // use self.addr() for unpredictable address to use for checking consistency below
         let invalid_ptr = core::ptr::without_provenance_mut::<R>((self as *mut Self).addr());
         let trait_ptr = <T as CastFrom<R>>::cast(invalid_ptr);
@@ -409,23 +537,138 @@ impl<T: ?Sized> MetaTable<T> {
         );
         let vtable = core::ptr::metadata(trait_ptr);
 
-        // Important: ensure no entry exists twice!
-        let len = self.indices.len();
-        match self.indices.entry(ty_id) {
-            Entry::Occupied(occ) => {
-                let ind = *occ.get();
+        let index = insert_vtable(
+            &mut self.indices,
+            &mut self.vtables,
+            TypeId::of::<R>(),
+            vtable,
+        );
+        self.insert_id::<R>(id, index);
+    }
+
+    /// Registers every resource `R` currently in `world`, including the ones
+    /// inserted with a dynamic id.
+    ///
+    /// Resources inserted later on have to be registered separately.
+    #[cfg(not(feature = "nightly"))]
+    pub fn register_all<R>(&mut self, world: &World)
+    where
+        R: Resource,
+        T: CastFrom<R> + 'static,
+    {
+        for id in world.resource_ids::<R>() {
+            self.register_id::<R>(id);
+        }
+    }
 
-                self.vtables[ind] = vtable;
+    /// Registers every resource `R` currently in `world`, including the ones
+    /// inserted with a dynamic id.
+    ///
+    /// Resources inserted later on have to be registered separately.
+    #[cfg(feature = "nightly")]
+    pub fn register_all<R>(&mut self, world: &World)
+    where
+        R: Resource,
+        T: CastFrom<R> + 'static,
+        T: Pointee<Metadata = DynMetadata<T>>,
+    {
+        for id in world.resource_ids::<R>() {
+            self.register_id::<R>(id);
+        }
+    }
+
+    /// Removes the resource `R` and all its ids from the table.
+    ///
+    /// Returns `false` if `R` wasn't registered.
+    pub fn unregister<R: Resource>(&mut self) -> bool {
+        match self.indices.get(&TypeId::of::<R>()) {
+            Some(&index) => {
+                self.remove_type(index);
+
+                true
             }
-            Entry::Vacant(vac) => {
-                vac.insert(len);
+            None => false,
+        }
+    }
 
-                self.vtables.push(vtable);
-                self.tys.push(ty_id);
+    /// Removes the resource stored under `id` from the table, keeping other
+    /// resources of the same type.
+    ///
+    /// The type itself is removed together with its last id, as if
+    /// unregistered with `unregister`.
+    ///
+    /// Returns `false` if `id` wasn't registered.
+    pub fn unregister_id(&mut self, id: &ResourceId) -> bool {
+        let position = match self.ids.iter().position(|(registered, _)| registered == id) {
+            Some(position) => position,
+            None => return false,
+        };
+        let (_, index) = self.ids.remove(position);
+
+        if !self.ids.iter().any(|&(_, i)| i == index) {
+            self.remove_type(index);
+        }
+
+        true
+    }
+
+    /// Removes the type at `index` and all its ids.
+    fn remove_type(&mut self, index: usize) {
+        self.indices.remove(&self.types[index].0);
+        self.ids.retain(|&(_, i)| i != index);
+
+        // move the last type into the freed slot
+        #[cfg(not(feature = "nightly"))]
+        self.vtable_fns.swap_remove(index);
+        #[cfg(feature = "nightly")]
+        self.vtables.swap_remove(index);
+        self.types.swap_remove(index);
+
+        let last = self.types.len();
+        if index != last {
+            *self.indices.get_mut(&self.types[index].0).unwrap() = index;
+            for (_, i) in self.ids.iter_mut().filter(|(_, i)| *i == last) {
+                *i = index;
             }
         }
     }
 
+    /// Returns `true` if the resource `R` is registered.
+    pub fn contains<R: Resource>(&self) -> bool {
+        self.indices.contains_key(&TypeId::of::<R>())
+    }
+
+    /// The number of registered resource types.
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Returns `true` if no resource is registered.
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    /// Iterates the registered resource types and their names, whether they
+    /// are in the `World` or not.
+    pub fn types(&self) -> impl Iterator<Item = (TypeId, &'static str)> + '_ {
+        self.types.iter().copied()
+    }
+
+    /// Iterates the registered ids, whether they are in the `World` or not.
+    pub fn ids(&self) -> impl Iterator<Item = &ResourceId> + '_ {
+        self.ids.iter().map(|(id, _)| id)
+    }
+
+    fn insert_id<R: Resource>(&mut self, id: ResourceId, index: usize) {
+        if index == self.types.len() {
+            self.types.push((TypeId::of::<R>(), std::any::type_name::<R>()));
+        }
+
+        if !self.ids.iter().any(|(registered, _)| *registered == id) {
+            self.ids.push((id, index));
+        }
+    }
+
     /// Tries to convert `world` to a trait object of type `&T`.
     /// If `world` doesn't have an implementation for `T` (or it wasn't
     /// registered), this will return `None`.
@@ -514,11 +757,18 @@ impl<T: ?Sized> MetaTable<T> {
             vtables: &self.vtables,
             index: 0,
             world: res,
-            tys: &self.tys,
+            ids: &self.ids,
+            declared: None,
             marker: PhantomData,
         }
     }
 
+    /// Iterates all resources that implement `T` and were registered,
+    /// together with their ids.
+    pub fn iter_with_ids<'a>(&'a self, res: &'a World) -> MetaIterWithIds<'a, T> {
+        MetaIterWithIds(self.iter(res))
+    }
+
     /// Iterates all resources that implement `T` and were registered mutably.
     pub fn iter_mut<'a>(&'a self, res: &'a World) -> MetaIterMut<'a, T> {
         MetaIterMut {
@@ -528,7 +778,8 @@ impl<T: ?Sized> MetaTable<T> {
             vtables: &self.vtables,
             index: 0,
             world: res,
-            tys: &self.tys,
+            ids: &self.ids,
+            declared: None,
             marker: PhantomData,
         }
     }
@@ -545,18 +796,307 @@ where
             #[cfg(feature = "nightly")]
             vtables: Default::default(),
             indices: Default::default(),
-            tys: Default::default(),
+            ids: Default::default(),
+            types: Default::default(),
             marker: Default::default(),
         }
     }
 }
 
+/// Stores `vtable` as the vtable of the type `ty`, returning its index.
+fn insert_vtable<V>(
+    indices: &mut HashMap<TypeId, usize>,
+    vtables: &mut Vec<V>,
+    ty: TypeId,
+    vtable: V,
+) -> usize {
+    // Important: ensure no entry exists twice!
+    let len = indices.len();
+    match indices.entry(ty) {
+        Entry::Occupied(occ) => {
+            let ind = *occ.get();
+
+            vtables[ind] = vtable;
+
+            ind
+        }
+        Entry::Vacant(vac) => {
+            vac.insert(len);
+
+            vtables.push(vtable);
+
+            len
+        }
+    }
+}
+
 fn assert_unsized<T: ?Sized>() {
     use core::mem::size_of;
 
     assert_eq!(size_of::<&T>(), 2 * size_of::<usize>());
 }
 
+/// The implementors of `T` a [`ReadAll`] or [`WriteAll`] accesses.
+struct Implementors<T: ?Sized> {
+    /// Sorted, shared with every fetched `ReadAll` or `WriteAll`.
+    ids: Arc<[ResourceId]>,
+    marker: PhantomData<Invariant<T>>,
+}
+
+impl<T: ?Sized + 'static> Implementors<T> {
+    fn new(table: &MetaTable<T>) -> Self {
+        let mut ids = table.ids().cloned().collect::<Vec<_>>();
+        ids.sort();
+
+        Implementors {
+            ids: ids.into(),
+            marker: PhantomData,
+        }
+    }
+
+    fn table_id() -> ResourceId {
+        ResourceId::new::<MetaTable<T>>()
+    }
+}
+
+impl<T: ?Sized> Clone for Implementors<T> {
+    fn clone(&self) -> Self {
+        Implementors {
+            ids: self.ids.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// The [`Accessor`] of [`ReadAll`], declaring read access to the
+/// `MetaTable<T>` and the implementors registered in it.
+///
+/// Iterating panics if an implementor registered after creating the accessor
+/// is in the `World`, since the accessor doesn't declare it.
+pub struct ReadAllAccessor<T: ?Sized>(Implementors<T>);
+
+impl<T: ?Sized + 'static> ReadAllAccessor<T> {
+    /// Creates an accessor for the implementors currently registered in
+    /// `table`.
+    pub fn new(table: &MetaTable<T>) -> Self {
+        ReadAllAccessor(Implementors::new(table))
+    }
+}
+
+impl<T: ?Sized> Clone for ReadAllAccessor<T> {
+    fn clone(&self) -> Self {
+        ReadAllAccessor(self.0.clone())
+    }
+}
+
+impl<T: ?Sized + 'static> Accessor for ReadAllAccessor<T> {
+    fn try_new() -> Option<Self> {
+        None
+    }
+
+    fn reads(&self) -> Vec<ResourceId> {
+        let mut reads = self.0.ids.to_vec();
+        reads.push(Implementors::<T>::table_id());
+
+        reads
+    }
+
+    fn writes(&self) -> Vec<ResourceId> {
+        Vec::new()
+    }
+
+    fn extend_reads<E: Extend<ResourceId>>(&self, reads: &mut E) {
+        reads.extend(self.0.ids.iter().cloned());
+        reads.extend(Some(Implementors::<T>::table_id()));
+    }
+
+    fn extend_writes<E: Extend<ResourceId>>(&self, _: &mut E) {}
+}
+
+/// The [`Accessor`] of [`WriteAll`], declaring read access to the
+/// `MetaTable<T>` and write access to the implementors registered in it.
+///
+/// Iterating panics if an implementor registered after creating the accessor
+/// is in the `World`, since the accessor doesn't declare it.
+pub struct WriteAllAccessor<T: ?Sized>(Implementors<T>);
+
+impl<T: ?Sized + 'static> WriteAllAccessor<T> {
+    /// Creates an accessor for the implementors currently registered in
+    /// `table`.
+    pub fn new(table: &MetaTable<T>) -> Self {
+        WriteAllAccessor(Implementors::new(table))
+    }
+}
+
+impl<T: ?Sized> Clone for WriteAllAccessor<T> {
+    fn clone(&self) -> Self {
+        WriteAllAccessor(self.0.clone())
+    }
+}
+
+impl<T: ?Sized + 'static> Accessor for WriteAllAccessor<T> {
+    fn try_new() -> Option<Self> {
+        None
+    }
+
+    fn reads(&self) -> Vec<ResourceId> {
+        vec![Implementors::<T>::table_id()]
+    }
+
+    fn writes(&self) -> Vec<ResourceId> {
+        self.0.ids.to_vec()
+    }
+
+    fn extend_reads<E: Extend<ResourceId>>(&self, reads: &mut E) {
+        reads.extend(Some(Implementors::<T>::table_id()));
+    }
+
+    fn extend_writes<E: Extend<ResourceId>>(&self, writes: &mut E) {
+        writes.extend(self.0.ids.iter().cloned());
+    }
+}
+
+/// `SystemData` giving read access to all resources implementing `T`, using
+/// the `MetaTable<T>` of the `World`. Setting it up inserts an empty
+/// `MetaTable<T>` if there is none yet.
+///
+/// The accessed resources are declared by a [`ReadAllAccessor`], which the
+/// system has to return from [`System::accessor`](crate::System::accessor).
+///
+/// # Examples
+///
+/// ```
+/// use shred::{
+///     AccessorCow, DispatcherBuilder, MetaTable, ReadAll, ReadAllAccessor, System, World,
+///     cast_from,
+/// };
+///
+/// trait Weight {
+///     fn weight(&self) -> u32;
+/// }
+///
+/// cast_from!(dyn Weight);
+///
+/// struct Cargo(u32);
+///
+/// impl Weight for Cargo {
+///     fn weight(&self) -> u32 {
+///         self.0
+///     }
+/// }
+///
+/// struct TotalWeight {
+///     access: ReadAllAccessor<dyn Weight>,
+///     total: u32,
+/// }
+///
+/// impl<'a> System<'a> for TotalWeight {
+///     type SystemData = ReadAll<'a, dyn Weight>;
+///
+///     fn run(&mut self, weights: Self::SystemData) {
+///         self.total = weights.iter().map(|w| w.weight()).sum();
+///     }
+///
+///     fn accessor<'b>(&'b self) -> AccessorCow<'a, 'b, Self> {
+///         AccessorCow::Ref(&self.access)
+///     }
+/// }
+///
+/// let mut table = MetaTable::<dyn Weight>::new();
+/// table.register::<Cargo>();
+///
+/// let system = TotalWeight {
+///     access: ReadAllAccessor::new(&table),
+///     total: 0,
+/// };
+///
+/// let mut world = World::empty();
+/// world.insert(table);
+/// world.insert(Cargo(12));
+///
+/// let mut dispatcher = DispatcherBuilder::new()
+///     .with(system, "total_weight", &[])
+///     .build();
+/// dispatcher.dispatch(&world);
+/// ```
+pub struct ReadAll<'a, T: ?Sized + 'static> {
+    declared: Arc<[ResourceId]>,
+    table: Fetch<'a, MetaTable<T>>,
+    world: &'a World,
+}
+
+impl<'a, T: ?Sized + 'static> ReadAll<'a, T> {
+    /// Iterates the declared implementors of `T`.
+    pub fn iter(&self) -> MetaIter<'_, T> {
+        MetaIter {
+            declared: Some(&self.declared),
+            ..self.table.iter(self.world)
+        }
+    }
+}
+
+impl<'a, T: ?Sized + 'static> DynamicSystemData<'a> for ReadAll<'a, T> {
+    type Accessor = ReadAllAccessor<T>;
+
+    fn setup(_accessor: &Self::Accessor, world: &mut World) {
+        world.entry::<MetaTable<T>>().or_insert_with(MetaTable::new);
+    }
+
+    fn fetch(access: &Self::Accessor, world: &'a World) -> Self {
+        ReadAll {
+            declared: access.0.ids.clone(),
+            table: world.fetch(),
+            world,
+        }
+    }
+}
+
+/// `SystemData` giving write access to all resources implementing `T`, using
+/// the `MetaTable<T>` of the `World`.
+///
+/// The accessed resources are declared by a [`WriteAllAccessor`], which the
+/// system has to return from [`System::accessor`](crate::System::accessor).
+/// See [`ReadAll`] for an example.
+pub struct WriteAll<'a, T: ?Sized + 'static> {
+    declared: Arc<[ResourceId]>,
+    table: Fetch<'a, MetaTable<T>>,
+    world: &'a World,
+}
+
+impl<'a, T: ?Sized + 'static> WriteAll<'a, T> {
+    /// Iterates the declared implementors of `T`.
+    pub fn iter(&self) -> MetaIter<'_, T> {
+        MetaIter {
+            declared: Some(&self.declared),
+            ..self.table.iter(self.world)
+        }
+    }
+
+    /// Iterates the declared implementors of `T` mutably.
+    pub fn iter_mut(&mut self) -> MetaIterMut<'_, T> {
+        MetaIterMut {
+            declared: Some(&self.declared),
+            ..self.table.iter_mut(self.world)
+        }
+    }
+}
+
+impl<'a, T: ?Sized + 'static> DynamicSystemData<'a> for WriteAll<'a, T> {
+    type Accessor = WriteAllAccessor<T>;
+
+    fn setup(_accessor: &Self::Accessor, world: &mut World) {
+        world.entry::<MetaTable<T>>().or_insert_with(MetaTable::new);
+    }
+
+    fn fetch(access: &Self::Accessor, world: &'a World) -> Self {
+        WriteAll {
+            declared: access.0.ids.clone(),
+            table: world.fetch(),
+            world,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -567,14 +1107,7 @@ mod tests {
         fn method2(&mut self, x: i32);
     }
 
-    unsafe impl<T> CastFrom<T> for dyn Object
-    where
-        T: Object + 'static,
-    {
-        fn cast(t: *mut T) -> *mut Self {
-            t
-        }
-    }
+    crate::cast_from!(dyn Object);
 
     struct ImplementorA(i32);
 
@@ -600,6 +1133,18 @@ mod tests {
         }
     }
 
+    trait Shared: Send + Sync {
+        fn value(&self) -> i32;
+    }
+
+    crate::cast_from!(dyn Shared + Send + Sync);
+
+    impl Shared for ImplementorA {
+        fn value(&self) -> i32 {
+            self.0
+        }
+    }
+
     #[test]
     fn test_iter_all() {
         let mut world = World::empty();
@@ -708,4 +1253,172 @@ mod tests {
         // Make sure it fulfills `Resource` requirements
         world.insert(table);
     }
+
+    #[test]
+    fn cast_from_with_bounds() {
+        let mut world = World::empty();
+        world.insert(ImplementorA(7));
+
+        let mut table = MetaTable::<dyn Shared + Send + Sync>::new();
+        table.register::<ImplementorA>();
+
+        assert_eq!(table.iter(&world).next().unwrap().value(), 7);
+    }
+
+    struct Doubler(WriteAllAccessor<dyn Object>);
+
+    impl<'a> crate::System<'a> for Doubler {
+        type SystemData = WriteAll<'a, dyn Object>;
+
+        fn run(&mut self, mut objects: Self::SystemData) {
+            for mut object in objects.iter_mut() {
+                object.method2(2);
+            }
+        }
+
+        fn accessor<'b>(&'b self) -> crate::AccessorCow<'a, 'b, Self> {
+            crate::AccessorCow::Ref(&self.0)
+        }
+    }
+
+    #[test]
+    fn write_all() {
+        let mut table = MetaTable::<dyn Object>::new();
+        table.register::<ImplementorA>();
+        let accessor = WriteAllAccessor::new(&table);
+
+        assert_eq!(accessor.reads(), vec![ResourceId::new::<MetaTable<dyn Object>>()]);
+        assert_eq!(accessor.writes(), vec![ResourceId::new::<ImplementorA>()]);
+
+        let mut world = World::empty();
+        world.insert(table);
+        world.insert(ImplementorA(3));
+        world.insert(ImplementorB(3));
+
+        let mut dispatcher = crate::DispatcherBuilder::new()
+            .with(Doubler(accessor), "doubler", &[])
+            .build();
+        dispatcher.dispatch(&world);
+
+        // `ImplementorB` isn't registered
+        assert_eq!(world.fetch::<ImplementorA>().0, 5);
+        assert_eq!(world.fetch::<ImplementorB>().0, 3);
+
+        // registered, but not in the `World`
+        world
+            .fetch_mut::<MetaTable<dyn Object>>()
+            .register_id::<ImplementorB>(ResourceId::new_with_dynamic_id::<ImplementorB>(1));
+        dispatcher.dispatch(&world);
+        assert_eq!(world.fetch::<ImplementorA>().0, 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "was registered after creating the accessor")]
+    fn write_all_registered_later() {
+        let mut table = MetaTable::<dyn Object>::new();
+        table.register::<ImplementorA>();
+        let accessor = WriteAllAccessor::new(&table);
+        table.register::<ImplementorB>();
+
+        let mut world = World::empty();
+        world.insert(table);
+        world.insert(ImplementorA(3));
+        world.insert(ImplementorB(3));
+
+        let mut dispatcher = crate::DispatcherBuilder::new()
+            .with(Doubler(accessor), "doubler", &[])
+            .build();
+        dispatcher.dispatch(&world);
+    }
+
+    #[test]
+    fn write_all_setup() {
+        let accessor = WriteAllAccessor::new(&MetaTable::<dyn Object>::new());
+
+        let mut world = World::empty();
+        let mut dispatcher = crate::DispatcherBuilder::new()
+            .with(Doubler(accessor), "doubler", &[])
+            .build();
+        dispatcher.setup(&mut world);
+        dispatcher.dispatch(&world);
+
+        assert!(world.has_value::<MetaTable<dyn Object>>());
+    }
+
+    #[test]
+    fn dynamic_ids() {
+        let mut world = World::empty();
+        world.insert(ImplementorA(1));
+        world.insert_by_id(ResourceId::new_with_dynamic_id::<ImplementorA>(1), ImplementorA(2));
+        world.insert_by_id(ResourceId::new_with_dynamic_id::<ImplementorB>(1), ImplementorB(3));
+        world.insert_by_id(ResourceId::new_with_dynamic_id::<ImplementorB>(2), ImplementorB(4));
+
+        let mut table = MetaTable::<dyn Object>::new();
+        table.register_all::<ImplementorA>(&world);
+        table.register_id::<ImplementorB>(ResourceId::new_with_dynamic_id::<ImplementorB>(2));
+        table.register_id::<ImplementorB>(ResourceId::new_with_dynamic_id::<ImplementorB>(2));
+
+        let mut values = table.iter(&world).map(|o| o.method1()).collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![1, 2, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Passed a `ResourceId` with a wrong type ID")]
+    fn register_wrong_id() {
+        let mut table = MetaTable::<dyn Object>::new();
+        table.register_id::<ImplementorA>(ResourceId::new::<ImplementorB>());
+    }
+
+    #[test]
+    fn unregister() {
+        let mut world = World::empty();
+        world.insert(ImplementorA(1));
+        world.insert(ImplementorB(2));
+        world.insert_by_id(ResourceId::new_with_dynamic_id::<ImplementorB>(1), ImplementorB(3));
+
+        let mut table = MetaTable::<dyn Object>::new();
+        table.register::<ImplementorA>();
+        table.register_all::<ImplementorB>(&world);
+        assert_eq!(table.len(), 2);
+
+        assert!(table.unregister::<ImplementorA>());
+        assert!(!table.unregister::<ImplementorA>());
+        assert!(!table.contains::<ImplementorA>());
+        assert!(table.contains::<ImplementorB>());
+        assert_eq!(table.len(), 1);
+        assert_eq!(
+            table.types().collect::<Vec<_>>(),
+            vec![(TypeId::of::<ImplementorB>(), std::any::type_name::<ImplementorB>())]
+        );
+
+        // `ImplementorB` took the place of `ImplementorA`
+        assert_eq!(table.get(&ImplementorB(5)).unwrap().method1(), 5);
+        let mut values = table.iter(&world).map(|o| o.method1()).collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![2, 3]);
+
+        assert!(table.unregister_id(&ResourceId::new::<ImplementorB>()));
+        let values = table
+            .iter_with_ids(&world)
+            .map(|(id, o)| (id, o.method1()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![(ResourceId::new_with_dynamic_id::<ImplementorB>(1), 3)]
+        );
+
+        // the type is removed together with its last id
+        let last = ResourceId::new_with_dynamic_id::<ImplementorB>(1);
+        assert!(table.unregister_id(&last));
+        assert!(!table.unregister_id(&last));
+        assert!(!table.contains::<ImplementorB>());
+        assert!(table.is_empty());
+        assert_eq!(table.types().count(), 0);
+        assert_eq!(table.iter(&world).count(), 0);
+        assert!(!table.unregister::<ImplementorB>());
+
+        table.register::<ImplementorA>();
+        assert_eq!(table.iter(&world).map(|o| o.method1()).collect::<Vec<_>>(), vec![1]);
+    }
 }
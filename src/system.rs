@@ -1,6 +1,9 @@
-use std::{marker::PhantomData, ops::Deref};
+use std::{any::Any, error::Error, marker::PhantomData, ops::Deref};
 
-use crate::{ResourceId, World};
+use crate::{
+    ResourceId, World,
+    world::{accumulate, unchecked},
+};
 
 /// A trait for accessing read/write multiple resources from a system. This can
 /// be used to create dynamic systems that don't specify what they fetch at
@@ -45,6 +48,30 @@ pub trait Accessor: Sized {
     ///
     /// [`ResourceId`]: struct.ResourceId.html
     fn writes(&self) -> Vec<ResourceId>;
+
+    /// Appends the [`ResourceId`]s returned by `reads` to `reads`.
+    ///
+    /// [`StaticAccessor`] implements this without allocating.
+    fn extend_reads<E: Extend<ResourceId>>(&self, reads: &mut E) {
+        reads.extend(self.reads());
+    }
+
+    /// Appends the [`ResourceId`]s returned by `writes` to `writes`.
+    ///
+    /// [`StaticAccessor`] implements this without allocating.
+    fn extend_writes<E: Extend<ResourceId>>(&self, writes: &mut E) {
+        writes.extend(self.writes());
+    }
+
+    /// Appends the [`ResourceId`]s the bundle accumulates into to
+    /// `accumulates`, see [`Accumulate`](crate::Accumulate).
+    ///
+    /// Accumulating systems don't conflict with each other, but can't run in
+    /// the same stage as systems reading or writing the resource. Appends
+    /// nothing by default.
+    fn extend_accumulates<E: Extend<ResourceId>>(&self, accumulates: &mut E) {
+        let _ = accumulates;
+    }
 }
 
 impl Accessor for () {
@@ -119,6 +146,24 @@ pub trait RunNow<'a> {
     /// tries to write to a resource which is read from).
     fn run_now(&mut self, world: &'a World);
 
+    /// Runs the system as part of a dispatcher.
+    ///
+    /// Fetches the `SystemData` without the runtime borrow checking if
+    /// `unchecked` is set and the build supports unchecked fetches, and
+    /// leaves merging the accumulated values to the stage if `in_stage` is
+    /// set.
+    ///
+    /// # Safety
+    ///
+    /// If `unchecked` is set, no resource in the `SystemData` of the system
+    /// may be borrowed in a conflicting way while it runs.
+    #[doc(hidden)]
+    unsafe fn run_dispatched(&mut self, world: &'a World, unchecked: bool, in_stage: bool) {
+        let _ = (unchecked, in_stage);
+
+        self.run_now(world);
+    }
+
     /// Sets up `World` for a later call to `run_now`.
     fn setup(&mut self, world: &mut World);
 
@@ -129,6 +174,16 @@ pub trait RunNow<'a> {
     fn dispose(self: Box<Self>, world: &mut World) {
         let _ = world;
     }
+
+    /// Returns the system as `Any`, see [`System::as_any`].
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+
+    /// Returns the system as `Any`, see [`System::as_any_mut`].
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        None
+    }
 }
 
 impl<'a, T> RunNow<'a> for T
@@ -138,6 +193,25 @@ where
     fn run_now(&mut self, world: &'a World) {
         let data = T::SystemData::fetch(&self.accessor(), world);
         self.run(data);
+
+        self.accessor()
+            .extend_accumulates(&mut accumulate::MergeInto(world));
+    }
+
+    unsafe fn run_dispatched(&mut self, world: &'a World, unchecked: bool, in_stage: bool) {
+        let data = if unchecked {
+            // Safety: guaranteed by the caller.
+            unsafe { unchecked::fetch(|| T::SystemData::fetch(&self.accessor(), world)) }
+        } else {
+            T::SystemData::fetch(&self.accessor(), world)
+        };
+        self.run(data);
+
+        // stages merge after all their systems ran instead
+        if !in_stage {
+            self.accessor()
+                .extend_accumulates(&mut accumulate::MergeInto(world));
+        }
     }
 
     fn setup(&mut self, world: &mut World) {
@@ -147,6 +221,14 @@ where
     fn dispose(self: Box<Self>, world: &mut World) {
         T::dispose(*self, world);
     }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        T::as_any(self)
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        T::as_any_mut(self)
+    }
 }
 
 #[repr(u8)]
@@ -207,6 +289,98 @@ pub trait System<'a> {
     {
         let _ = world;
     }
+
+    /// Returns the system as `Any`, so it can be accessed using
+    /// [`Dispatcher::system`](crate::Dispatcher::system) after the dispatcher
+    /// has been built.
+    ///
+    /// Returns `None` by default; `'static` systems which want to be
+    /// accessible return `Some(self)`.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+
+    /// Mutable version of [`as_any`](System::as_any), used by
+    /// [`Dispatcher::system_mut`](crate::Dispatcher::system_mut).
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        None
+    }
+}
+
+/// A fallible variant of [`System`], whose `run` returns a `Result`.
+///
+/// Add it using
+/// [`DispatcherBuilder::with_try`](crate::DispatcherBuilder::with_try). The
+/// errors of a dispatch are collected in its
+/// [`DispatchErrors`](crate::DispatchErrors), or handed to the
+/// [`ErrorHandler`](crate::ErrorHandler) resource if the `World` has one. This
+/// way, reporting an error doesn't require write access to a shared resource.
+///
+/// Unlike `System`, a `TrySystem` only supports static `SystemData`.
+///
+/// ## Examples
+///
+/// ```
+/// # use shred::{DispatcherBuilder, Read, TrySystem, World};
+/// struct Parse;
+///
+/// impl<'a> TrySystem<'a> for Parse {
+///     type Error = std::num::ParseIntError;
+///     type SystemData = Read<'a, String>;
+///
+///     fn run(&mut self, input: Self::SystemData) -> Result<(), Self::Error> {
+///         input.parse::<i32>()?;
+///
+///         Ok(())
+///     }
+/// }
+///
+/// let mut world = World::empty();
+/// let mut dispatcher = DispatcherBuilder::new().with_try(Parse, "parse", &[]).build();
+/// dispatcher.setup(&mut world);
+/// dispatcher.dispatch(&world);
+///
+/// assert_eq!(dispatcher.errors().errors()[0].name(), "parse");
+/// ```
+pub trait TrySystem<'a> {
+    /// The resource bundle required to execute this system.
+    type SystemData: SystemData<'a>;
+
+    /// The error this system may fail with.
+    type Error: Into<Box<dyn Error + Send + Sync>>;
+
+    /// Executes the system with the required system data.
+    fn run(&mut self, data: Self::SystemData) -> Result<(), Self::Error>;
+
+    /// Returns a hint how long the system needs for running, see
+    /// [`System::running_time`].
+    fn running_time(&self) -> RunningTime {
+        RunningTime::Average
+    }
+
+    /// Sets up the `World` using `Self::SystemData::setup`.
+    fn setup(&mut self, world: &mut World) {
+        <Self::SystemData as SystemData>::setup(world)
+    }
+
+    /// Performs clean up that requires resources from the `World`, see
+    /// [`System::dispose`].
+    fn dispose(self, world: &mut World)
+    where
+        Self: Sized,
+    {
+        let _ = world;
+    }
+
+    /// Returns the system as `Any`, see [`System::as_any`].
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+
+    /// Returns the system as `Any`, see [`System::as_any_mut`].
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        None
+    }
 }
 
 /// A static system data that can specify its dependencies at statically (at
@@ -284,6 +458,31 @@ pub trait SystemData<'a> {
     ///
     /// Please note that returning wrong dependencies can lead to a panic.
     fn writes() -> Vec<ResourceId>;
+
+    /// Appends all read dependencies to `reads`, which doesn't allocate if
+    /// `reads` is e.g. a `SmallVec` with enough inline capacity.
+    ///
+    /// The default implementation calls `reads`; the system data of this
+    /// crate and `#[derive(SystemData)]` append their dependencies directly.
+    fn extend_reads<E: Extend<ResourceId>>(reads: &mut E) {
+        reads.extend(Self::reads());
+    }
+
+    /// Appends all write dependencies to `writes`, which doesn't allocate if
+    /// `writes` is e.g. a `SmallVec` with enough inline capacity.
+    ///
+    /// The default implementation calls `writes`; the system data of this
+    /// crate and `#[derive(SystemData)]` append their dependencies directly.
+    fn extend_writes<E: Extend<ResourceId>>(writes: &mut E) {
+        writes.extend(Self::writes());
+    }
+
+    /// Appends the resources accumulated into by `Self::fetch` to
+    /// `accumulates`, see [`Accumulate`](crate::Accumulate). These are
+    /// neither reads nor writes.
+    ///
+    /// Appends nothing by default.
+    fn extend_accumulates<E: Extend<ResourceId>>(_: &mut E) {}
 }
 
 impl<'a, T> DynamicSystemData<'a> for T
@@ -313,6 +512,10 @@ impl<'a> SystemData<'a> for () {
     fn writes() -> Vec<ResourceId> {
         Vec::new()
     }
+
+    fn extend_reads<E: Extend<ResourceId>>(_: &mut E) {}
+
+    fn extend_writes<E: Extend<ResourceId>>(_: &mut E) {}
 }
 
 /// The static accessor that is used for `SystemData`.
@@ -338,6 +541,18 @@ where
     fn writes(&self) -> Vec<ResourceId> {
         T::writes()
     }
+
+    fn extend_reads<E: Extend<ResourceId>>(&self, reads: &mut E) {
+        T::extend_reads(reads);
+    }
+
+    fn extend_writes<E: Extend<ResourceId>>(&self, writes: &mut E) {
+        T::extend_writes(writes);
+    }
+
+    fn extend_accumulates<E: Extend<ResourceId>>(&self, accumulates: &mut E) {
+        T::extend_accumulates(accumulates);
+    }
 }
 
 /// A struct implementing system data indicates that it bundles some resources
@@ -385,6 +600,10 @@ impl<T: ?Sized> SystemData<'_> for PhantomData<T> {
     fn writes() -> Vec<ResourceId> {
         Vec::new()
     }
+
+    fn extend_reads<E: Extend<ResourceId>>(_: &mut E) {}
+
+    fn extend_writes<E: Extend<ResourceId>>(_: &mut E) {}
 }
 
 macro_rules! impl_data {
@@ -397,7 +616,7 @@ macro_rules! impl_data {
 
                     $(
                         <$ty as SystemData>::setup(&mut *world);
-                     )*
+                    )*
                 }
 
                 fn fetch(world: &'a World) -> Self {
@@ -407,30 +626,36 @@ macro_rules! impl_data {
                 }
 
                 fn reads() -> Vec<ResourceId> {
-                    #![allow(unused_mut)]
-
                     let mut r = Vec::new();
-
-                    $( {
-                        let mut reads = <$ty as SystemData>::reads();
-                        r.append(&mut reads);
-                    } )*
+                    Self::extend_reads(&mut r);
 
                     r
                 }
 
                 fn writes() -> Vec<ResourceId> {
-                    #![allow(unused_mut)]
-
                     let mut r = Vec::new();
-
-                    $( {
-                        let mut writes = <$ty as SystemData>::writes();
-                        r.append(&mut writes);
-                    } )*
+                    Self::extend_writes(&mut r);
 
                     r
                 }
+
+                fn extend_reads<Ext: Extend<ResourceId>>(reads: &mut Ext) {
+                    $(
+                        <$ty as SystemData>::extend_reads(reads);
+                    )*
+                }
+
+                fn extend_writes<Ext: Extend<ResourceId>>(writes: &mut Ext) {
+                    $(
+                        <$ty as SystemData>::extend_writes(writes);
+                    )*
+                }
+
+                fn extend_accumulates<Ext: Extend<ResourceId>>(accumulates: &mut Ext) {
+                    $(
+                        <$ty as SystemData>::extend_accumulates(accumulates);
+                    )*
+                }
             }
     };
 }
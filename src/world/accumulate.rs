@@ -0,0 +1,270 @@
+use std::{
+    mem,
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+    thread,
+};
+
+use ahash::AHashMap as HashMap;
+
+use crate::{Fetch, Resource, ResourceId, SystemData, World};
+
+/// A resource whose values can be combined in any order, e.g. a sum or a
+/// maximum, which allows fetching it with [`Accumulate`].
+///
+/// `merge` has to be commutative and associative, and merging
+/// `Default::default()` must not change the value, since the order and
+/// number of merges depends on the schedule.
+///
+/// ## Examples
+///
+/// ```
+/// use shred::Merge;
+///
+/// #[derive(Default)]
+/// struct Stats {
+///     damage: u64,
+///     kills: u32,
+/// }
+///
+/// impl Merge for Stats {
+///     fn merge(&mut self, other: Self) {
+///         self.damage += other.damage;
+///         self.kills += other.kills;
+///     }
+/// }
+/// ```
+pub trait Merge: Resource + Default {
+    /// Merges `other` into `self`.
+    fn merge(&mut self, other: Self);
+}
+
+macro_rules! impl_merge_sum {
+    ( $($ty:ty),* ) => {
+        $(
+            impl Merge for $ty {
+                fn merge(&mut self, other: Self) {
+                    *self += other;
+                }
+            }
+        )*
+    };
+}
+
+impl_merge_sum!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64
+);
+
+/// Allows systems running in parallel to add into the same resource.
+///
+/// Every system run accumulates into its own value, starting at
+/// `T::default()`, which is merged into the resource of the `World` after
+/// the stage the system ran in. Systems accumulating into the same resource
+/// don't conflict with each other, but can't run in the same stage as
+/// systems reading or writing it, so they always see the merged value.
+///
+/// Systems run outside of a stage, e.g. with
+/// [`RunNow::run_now`](crate::RunNow::run_now) or as thread local systems,
+/// merge right after they ran, even if they are run from within a system of
+/// a stage. The `par!`/`seq!` API treats accumulating as writing.
+///
+/// If the system panics while the `Accumulate` is alive, e.g. with panic
+/// isolation enabled, its value is discarded instead of merged.
+///
+/// The resource is inserted with its default value on setup.
+///
+/// ## Examples
+///
+/// ```
+/// use shred::{Accumulate, DispatcherBuilder, System, World};
+///
+/// struct Hit(u64);
+///
+/// impl<'a> System<'a> for Hit {
+///     type SystemData = Accumulate<'a, u64>;
+///
+///     fn run(&mut self, mut damage: Self::SystemData) {
+///         *damage += self.0;
+///     }
+/// }
+///
+/// let mut dispatcher = DispatcherBuilder::new()
+///     .with(Hit(3), "sword", &[])
+///     .with(Hit(5), "arrow", &[])
+///     .build();
+///
+/// let mut world = World::empty();
+/// dispatcher.setup(&mut world);
+/// dispatcher.dispatch(&world);
+///
+/// assert_eq!(*world.fetch::<u64>(), 8);
+/// ```
+pub struct Accumulate<'a, T: Merge> {
+    pending: Fetch<'a, Pending<T>>,
+    value: T,
+}
+
+impl<T: Merge> Deref for Accumulate<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Merge> DerefMut for Accumulate<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: Merge> Drop for Accumulate<'_, T> {
+    fn drop(&mut self) {
+        // the value of a panicking system is incomplete
+        if thread::panicking() {
+            return;
+        }
+
+        let value = mem::take(&mut self.value);
+        let mut pending = self
+            .pending
+            .shard()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+
+        match *pending {
+            Some(ref mut pending) => pending.merge(value),
+            None => *pending = Some(value),
+        }
+    }
+}
+
+impl<'a, T: Merge> SystemData<'a> for Accumulate<'a, T> {
+    fn setup(world: &mut World) {
+        if !world.has_value::<T>() {
+            world.insert(T::default());
+        }
+        if !world.has_value::<Pending<T>>() {
+            world.insert(Pending::<T>::new());
+        }
+
+        world
+            .entry::<Mergers>()
+            .or_insert_with(Mergers::default)
+            .0
+            .insert(ResourceId::new::<T>(), merge_pending::<T>);
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        Accumulate {
+            pending: world.fetch(),
+            value: T::default(),
+        }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        vec![ResourceId::new::<Pending<T>>()]
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        vec![]
+    }
+
+    fn extend_reads<E: Extend<ResourceId>>(reads: &mut E) {
+        reads.extend(Some(ResourceId::new::<Pending<T>>()));
+    }
+
+    fn extend_writes<E: Extend<ResourceId>>(_: &mut E) {}
+
+    fn extend_accumulates<E: Extend<ResourceId>>(accumulates: &mut E) {
+        accumulates.extend(Some(ResourceId::new::<T>()));
+    }
+}
+
+/// The merged values of the `Accumulate<T>`s which ran since the last merge.
+///
+/// Every thread of the thread pool merges into its own shard, so systems
+/// running in parallel don't contend for a lock.
+struct Pending<T>(Box<[Shard<T>]>);
+
+/// Aligned to keep the shards of different threads on separate cache lines.
+#[repr(align(64))]
+struct Shard<T>(Mutex<Option<T>>);
+
+impl<T> Pending<T> {
+    fn new() -> Self {
+        Pending((0..shards()).map(|_| Shard(Mutex::new(None))).collect())
+    }
+
+    /// Returns the shard of the current thread.
+    fn shard(&self) -> &Mutex<Option<T>> {
+        &self.0[thread_index() % self.0.len()].0
+    }
+}
+
+/// One shard for every thread of the pool and one for all other threads.
+#[cfg(feature = "parallel")]
+fn shards() -> usize {
+    rayon::current_num_threads() + 1
+}
+
+#[cfg(feature = "parallel")]
+fn thread_index() -> usize {
+    rayon::current_thread_index().map_or(0, |index| index + 1)
+}
+
+#[cfg(not(feature = "parallel"))]
+fn shards() -> usize {
+    1
+}
+
+#[cfg(not(feature = "parallel"))]
+fn thread_index() -> usize {
+    0
+}
+
+/// The function merging the pending values of every accumulated resource.
+#[derive(Default)]
+struct Mergers(HashMap<ResourceId, fn(&World)>);
+
+fn merge_pending<T: Merge>(world: &World) {
+    let pending = world.fetch::<Pending<T>>();
+    let mut values = pending
+        .0
+        .iter()
+        .filter_map(|shard| shard.0.lock().unwrap_or_else(|e| e.into_inner()).take())
+        .peekable();
+
+    if values.peek().is_some() {
+        let mut resource = world.fetch_mut::<T>();
+        for value in values {
+            resource.merge(value);
+        }
+    }
+}
+
+/// Merges the pending values of the resources `ids` into the `World`.
+pub(crate) fn merge(world: &World, ids: impl IntoIterator<Item = ResourceId>) {
+    let mut ids = ids.into_iter().peekable();
+    if ids.peek().is_none() {
+        return;
+    }
+
+    if let Some(mergers) = world.try_fetch::<Mergers>() {
+        for id in ids {
+            if let Some(merge) = mergers.0.get(&id) {
+                merge(world);
+            }
+        }
+    }
+}
+
+/// Merges the pending values of the resources it's extended with, e.g. by
+/// `Accessor::extend_accumulates`.
+pub(crate) struct MergeInto<'a>(pub &'a World);
+
+impl Extend<ResourceId> for MergeInto<'_> {
+    fn extend<I: IntoIterator<Item = ResourceId>>(&mut self, ids: I) {
+        merge(self.0, ids);
+    }
+}
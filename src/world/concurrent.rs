@@ -0,0 +1,181 @@
+use std::{
+    marker::PhantomData,
+    ops::Deref,
+    sync::{
+        Mutex, RwLock,
+        atomic::{
+            AtomicBool, AtomicI8, AtomicI16, AtomicI32, AtomicI64, AtomicIsize, AtomicU8,
+            AtomicU16, AtomicU32, AtomicU64, AtomicUsize,
+        },
+    },
+};
+
+use crate::{DefaultProvider, Fetch, Resource, ResourceId, SetupHandler, SystemData, World};
+
+/// Marker trait for resources which synchronize mutation internally, e.g.
+/// atomics, locks or lock-free queues, and may therefore be fetched with
+/// [`Concurrent`].
+///
+/// Implement it for your own internally synchronized resources:
+///
+/// ```
+/// use std::sync::atomic::AtomicU32;
+///
+/// use shred::ConcurrentResource;
+///
+/// #[derive(Default)]
+/// struct Spawned {
+///     count: AtomicU32,
+/// }
+///
+/// impl ConcurrentResource for Spawned {}
+/// ```
+pub trait ConcurrentResource: Resource + Sync {}
+
+macro_rules! impl_concurrent_resource {
+    ( $($ty:ty),* ) => {
+        $(
+            impl ConcurrentResource for $ty {}
+        )*
+    };
+}
+
+impl_concurrent_resource!(
+    AtomicBool,
+    AtomicI8,
+    AtomicI16,
+    AtomicI32,
+    AtomicI64,
+    AtomicIsize,
+    AtomicU8,
+    AtomicU16,
+    AtomicU32,
+    AtomicU64,
+    AtomicUsize
+);
+
+impl<T> ConcurrentResource for Mutex<T> where T: Send + 'static {}
+
+impl<T> ConcurrentResource for RwLock<T> where T: Send + Sync + 'static {}
+
+/// A [`Read`](crate::Read) which only accepts internally synchronized
+/// resources, to document that the system mutates the resource through `&T`.
+///
+/// `Concurrent<T>` fetches, sets up and schedules exactly like `Read<T>`:
+/// the resource is declared as read, so it doesn't conflict with other
+/// reads and still conflicts with `Write`. The only difference is the
+/// [`ConcurrentResource`] bound.
+///
+/// If the resource isn't strictly required, you should use
+/// `Option<Concurrent<T>>`.
+///
+/// # Type parameters
+///
+/// * `T`: The type of the resource
+/// * `F`: The setup handler (default: `DefaultProvider`)
+///
+/// ## Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicU32, Ordering};
+///
+/// use shred::{Concurrent, DispatcherBuilder, System, World};
+///
+/// struct Spawner;
+///
+/// impl<'a> System<'a> for Spawner {
+///     type SystemData = Concurrent<'a, AtomicU32>;
+///
+///     fn run(&mut self, spawned: Self::SystemData) {
+///         spawned.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let mut dispatcher = DispatcherBuilder::new()
+///     .with(Spawner, "a", &[])
+///     .with(Spawner, "b", &[])
+///     .build();
+///
+/// let mut world = World::empty();
+/// dispatcher.setup(&mut world);
+/// dispatcher.dispatch(&world);
+///
+/// assert_eq!(world.fetch::<AtomicU32>().load(Ordering::Relaxed), 2);
+/// ```
+pub struct Concurrent<'a, T: 'a, F = DefaultProvider> {
+    inner: Fetch<'a, T>,
+    phantom: PhantomData<F>,
+}
+
+impl<T, F> Deref for Concurrent<'_, T, F>
+where
+    T: ConcurrentResource,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T, F> From<Fetch<'a, T>> for Concurrent<'a, T, F> {
+    fn from(inner: Fetch<'a, T>) -> Self {
+        Concurrent {
+            inner,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, F> SystemData<'a> for Concurrent<'a, T, F>
+where
+    T: ConcurrentResource,
+    F: SetupHandler<T>,
+{
+    fn setup(world: &mut World) {
+        F::setup(world)
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        world.fetch::<T>().into()
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        vec![ResourceId::new::<T>()]
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        vec![]
+    }
+
+    fn extend_reads<E: Extend<ResourceId>>(reads: &mut E) {
+        reads.extend(Some(ResourceId::new::<T>()));
+    }
+
+    fn extend_writes<E: Extend<ResourceId>>(_: &mut E) {}
+}
+
+impl<'a, T, F> SystemData<'a> for Option<Concurrent<'a, T, F>>
+where
+    T: ConcurrentResource,
+{
+    fn setup(_: &mut World) {}
+
+    fn fetch(world: &'a World) -> Self {
+        world.try_fetch().map(Into::into)
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        vec![ResourceId::new::<T>()]
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        vec![]
+    }
+
+    fn extend_reads<E: Extend<ResourceId>>(reads: &mut E) {
+        reads.extend(Some(ResourceId::new::<T>()));
+    }
+
+    fn extend_writes<E: Extend<ResourceId>>(_: &mut E) {}
+}
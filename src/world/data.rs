@@ -61,6 +61,12 @@ where
     fn writes() -> Vec<ResourceId> {
         vec![]
     }
+
+    fn extend_reads<E: Extend<ResourceId>>(reads: &mut E) {
+        reads.extend(Some(ResourceId::new::<T>()));
+    }
+
+    fn extend_writes<E: Extend<ResourceId>>(_: &mut E) {}
 }
 
 /// Allows to fetch a resource in a system mutably.
@@ -125,6 +131,12 @@ where
     fn writes() -> Vec<ResourceId> {
         vec![ResourceId::new::<T>()]
     }
+
+    fn extend_reads<E: Extend<ResourceId>>(_: &mut E) {}
+
+    fn extend_writes<E: Extend<ResourceId>>(writes: &mut E) {
+        writes.extend(Some(ResourceId::new::<T>()));
+    }
 }
 
 // ------------------
@@ -146,6 +158,12 @@ where
     fn writes() -> Vec<ResourceId> {
         vec![]
     }
+
+    fn extend_reads<E: Extend<ResourceId>>(reads: &mut E) {
+        reads.extend(Some(ResourceId::new::<T>()));
+    }
+
+    fn extend_writes<E: Extend<ResourceId>>(_: &mut E) {}
 }
 
 impl<'a, T, F> SystemData<'a> for Option<Write<'a, T, F>>
@@ -165,6 +183,12 @@ where
     fn writes() -> Vec<ResourceId> {
         vec![ResourceId::new::<T>()]
     }
+
+    fn extend_reads<E: Extend<ResourceId>>(_: &mut E) {}
+
+    fn extend_writes<E: Extend<ResourceId>>(writes: &mut E) {
+        writes.extend(Some(ResourceId::new::<T>()));
+    }
 }
 
 /// Allows to fetch a resource in a system immutably.
@@ -1,11 +1,6 @@
 use std::marker::PhantomData;
 
-use crate::{
-    cell::{AtomicRefCell, AtomicRefMut},
-    world::{FetchMut, Resource, ResourceId},
-};
-
-type StdEntry<'a, K, V> = std::collections::hash_map::Entry<'a, K, V>;
+use crate::world::{FetchMut, Resource, ResourceId, World};
 
 /// An entry to a resource of the `World` struct.
 /// This is similar to the Entry API found in the standard library.
@@ -24,7 +19,8 @@ type StdEntry<'a, K, V> = std::collections::hash_map::Entry<'a, K, V>;
 /// println!("{:?}", value.0 * 2);
 /// ```
 pub struct Entry<'a, T: 'a> {
-    inner: StdEntry<'a, ResourceId, AtomicRefCell<Box<dyn Resource>>>,
+    id: ResourceId,
+    world: &'a mut World,
     marker: PhantomData<T>,
 }
 
@@ -46,23 +42,20 @@ where
     where
         F: FnOnce() -> T,
     {
-        let value = self
-            .inner
-            .or_insert_with(move || AtomicRefCell::new(Box::new(f())));
-        let inner = AtomicRefMut::map(value.borrow_mut(), Box::as_mut);
-
-        FetchMut {
-            inner,
-            phantom: PhantomData,
+        if !self.world.has_value_raw(self.id.clone()) {
+            self.world.insert_by_id(self.id.clone(), f());
         }
+
+        let world: &'a World = self.world;
+
+        FetchMut::borrow(world.cell(&self.id).unwrap())
     }
 }
 
-pub(super) fn create_entry<T>(
-    e: StdEntry<ResourceId, AtomicRefCell<Box<dyn Resource>>>,
-) -> Entry<T> {
+pub(super) fn create_entry<T>(world: &mut World, id: ResourceId) -> Entry<'_, T> {
     Entry {
-        inner: e,
+        id,
+        world,
         marker: PhantomData,
     }
 }
@@ -1,9 +1,12 @@
 //! Module for resource related types
 
 pub use self::{
+    accumulate::{Accumulate, Merge},
+    concurrent::{Concurrent, ConcurrentResource},
     data::{Read, ReadExpect, Write, WriteExpect},
     entry::Entry,
     setup::{DefaultProvider, PanicHandler, SetupHandler},
+    slot::ResourceSlot,
 };
 
 use std::{
@@ -15,15 +18,26 @@ use std::{
 use ahash::AHashMap as HashMap;
 
 use crate::SystemData;
-use crate::cell::{AtomicRef, AtomicRefCell, AtomicRefMut};
+use crate::cell::AtomicRefCell;
+use crate::meta::{CastFrom, MetaTable};
 
-use self::entry::create_entry;
+#[cfg(feature = "nightly")]
+use core::ptr::{DynMetadata, Pointee};
 
+use self::{
+    entry::create_entry,
+    unchecked::{Borrow, BorrowMut},
+};
+
+pub(crate) mod accumulate;
+mod concurrent;
 mod data;
 mod entry;
 mod res_downcast;
 #[macro_use]
 mod setup;
+mod slot;
+pub(crate) mod unchecked;
 
 /// Allows to fetch a resource in a system immutably.
 ///
@@ -33,10 +47,22 @@ mod setup;
 ///
 /// * `T`: The type of the resource
 pub struct Fetch<'a, T: 'a> {
-    inner: AtomicRef<'a, dyn Resource>,
+    inner: Borrow<'a>,
     phantom: PhantomData<&'a T>,
 }
 
+impl<'a, T> Fetch<'a, T>
+where
+    T: Resource,
+{
+    pub(super) fn borrow(cell: &'a AtomicRefCell<Box<dyn Resource>>) -> Self {
+        Fetch {
+            inner: Borrow::new::<T>(cell),
+            phantom: PhantomData,
+        }
+    }
+}
+
 impl<T> Deref for Fetch<'_, T>
 where
     T: Resource,
@@ -44,14 +70,14 @@ where
     type Target = T;
 
     fn deref(&self) -> &T {
-        unsafe { self.inner.downcast_ref_unchecked() }
+        unsafe { self.inner.get().downcast_ref_unchecked() }
     }
 }
 
 impl<T> Clone for Fetch<'_, T> {
     fn clone(&self) -> Self {
         Fetch {
-            inner: AtomicRef::clone(&self.inner),
+            inner: self.inner.clone(),
             phantom: PhantomData,
         }
     }
@@ -66,10 +92,22 @@ impl<T> Clone for Fetch<'_, T> {
 ///
 /// * `T`: The type of the resource
 pub struct FetchMut<'a, T: 'a> {
-    inner: AtomicRefMut<'a, dyn Resource>,
+    inner: BorrowMut<'a>,
     phantom: PhantomData<&'a mut T>,
 }
 
+impl<'a, T> FetchMut<'a, T>
+where
+    T: Resource,
+{
+    pub(super) fn borrow(cell: &'a AtomicRefCell<Box<dyn Resource>>) -> Self {
+        FetchMut {
+            inner: BorrowMut::new::<T>(cell),
+            phantom: PhantomData,
+        }
+    }
+}
+
 impl<T> Deref for FetchMut<'_, T>
 where
     T: Resource,
@@ -77,7 +115,7 @@ where
     type Target = T;
 
     fn deref(&self) -> &T {
-        unsafe { self.inner.downcast_ref_unchecked() }
+        unsafe { self.inner.get().downcast_ref_unchecked() }
     }
 }
 
@@ -86,7 +124,7 @@ where
     T: Resource,
 {
     fn deref_mut(&mut self) -> &mut T {
-        unsafe { self.inner.downcast_mut_unchecked() }
+        unsafe { self.inner.get_mut().downcast_mut_unchecked() }
     }
 }
 
@@ -158,7 +196,7 @@ impl ResourceId {
         }
     }
 
-    fn assert_same_type_id<R: Resource>(&self) {
+    pub(crate) fn assert_same_type_id<R: Resource>(&self) {
         let res_id0 = ResourceId::new::<R>();
         assert_eq!(
             res_id0.type_id, self.type_id,
@@ -189,9 +227,59 @@ impl ResourceId {
 /// # Resource Ids
 ///
 /// Resources are identified by `ResourceId`s, which consist of a `TypeId`.
+///
+/// # Resource Slots
+///
+/// Resources inserted with [`insert_slot`](World::insert_slot) are stored
+/// apart from the ones identified by `ResourceId`s, and are only fetched
+/// through the returned [`ResourceSlot`], without a hash lookup.
 #[derive(Default)]
 pub struct World {
     resources: HashMap<ResourceId, AtomicRefCell<Box<dyn Resource>>>,
+    /// The slots which are free to be reused.
+    free: Vec<usize>,
+    /// The resources inserted with `insert_slot`.
+    slots: Vec<Slot>,
+    /// Registers inserted resources of a type in `MetaTable`s, see
+    /// `register_meta`.
+    meta_hooks: HashMap<TypeId, Vec<MetaHook>>,
+}
+
+/// The storage of a single slot resource, `None` while the slot is free.
+struct Slot {
+    /// Incremented whenever the resource is removed, which invalidates the
+    /// `ResourceSlot`s returned before.
+    generation: usize,
+    id: ResourceId,
+    resource: Option<AtomicRefCell<Box<dyn Resource>>>,
+}
+
+/// Registers the resource with the given id in a `MetaTable`.
+type MetaHook = fn(&mut World, ResourceId);
+
+#[cfg(not(feature = "nightly"))]
+fn register_in_meta_table<T, R>(world: &mut World, id: ResourceId)
+where
+    R: Resource,
+    T: CastFrom<R> + ?Sized + 'static,
+{
+    world
+        .entry::<MetaTable<T>>()
+        .or_insert_with(MetaTable::new)
+        .register_id::<R>(id);
+}
+
+#[cfg(feature = "nightly")]
+fn register_in_meta_table<T, R>(world: &mut World, id: ResourceId)
+where
+    R: Resource,
+    T: CastFrom<R> + ?Sized + 'static,
+    T: Pointee<Metadata = DynMetadata<T>>,
+{
+    world
+        .entry::<MetaTable<T>>()
+        .or_insert_with(MetaTable::new)
+        .register_id::<R>(id);
 }
 
 impl World {
@@ -256,15 +344,15 @@ impl World {
 
     /// Returns true if the specified resource type exists in `self`.
     pub fn has_value_raw(&self, id: ResourceId) -> bool {
-        self.resources.contains_key(&id)
+        self.cell(&id).is_some()
     }
 
     /// Returns an entry for the resource with type `R`.
-    pub fn entry<R>(&mut self) -> Entry<R>
+    pub fn entry<R>(&mut self) -> Entry<'_, R>
     where
         R: Resource,
     {
-        create_entry(self.resources.entry(ResourceId::new::<R>()))
+        create_entry(self, ResourceId::new::<R>())
     }
 
     /// Gets `SystemData` `T` from the `World`. This can be used to retrieve
@@ -403,7 +491,7 @@ impl World {
     ///
     /// Panics if the resource doesn't exist.
     /// Panics if the resource is being accessed mutably.
-    pub fn fetch<T>(&self) -> Fetch<T>
+    pub fn fetch<T>(&self) -> Fetch<'_, T>
     where
         T: Resource,
     {
@@ -421,22 +509,13 @@ impl World {
 
     /// Like `fetch`, but returns an `Option` instead of inserting a default
     /// value in case the resource does not exist.
-    pub fn try_fetch<T>(&self) -> Option<Fetch<T>>
+    pub fn try_fetch<T>(&self) -> Option<Fetch<'_, T>>
     where
         T: Resource,
     {
         let res_id = ResourceId::new::<T>();
-        let resource = self.resources.get(&res_id)?;
-
-        let borrow = match resource.try_borrow() {
-            Ok(res) => res,
-            Err(e) => panic!("{}: {e}", std::any::type_name::<T>())
-        };
 
-        Some(Fetch {
-            inner: AtomicRef::map(borrow, Box::as_ref),
-            phantom: PhantomData,
-        })
+        self.cell(&res_id).map(Fetch::borrow)
     }
 
     /// Like `try_fetch`, but fetches the resource by its `ResourceId` which
@@ -448,16 +527,13 @@ impl World {
     /// # Panics
     ///
     /// This method panics if `id` refers to a different type ID than `T`.
-    pub fn try_fetch_by_id<T>(&self, id: ResourceId) -> Option<Fetch<T>>
+    pub fn try_fetch_by_id<T>(&self, id: ResourceId) -> Option<Fetch<'_, T>>
     where
         T: Resource,
     {
         id.assert_same_type_id::<T>();
 
-        self.resources.get(&id).map(|r| Fetch {
-            inner: AtomicRef::map(r.borrow(), Box::as_ref),
-            phantom: PhantomData,
-        })
+        self.cell(&id).map(Fetch::borrow)
     }
 
     /// Fetches the resource with the specified type `T` mutably.
@@ -468,7 +544,7 @@ impl World {
     ///
     /// Panics if the resource doesn't exist.
     /// Panics if the resource is already being accessed.
-    pub fn fetch_mut<T>(&self) -> FetchMut<T>
+    pub fn fetch_mut<T>(&self) -> FetchMut<'_, T>
     where
         T: Resource,
     {
@@ -477,21 +553,13 @@ impl World {
 
     /// Like `fetch_mut`, but returns an `Option` instead of inserting a default
     /// value in case the resource does not exist.
-    pub fn try_fetch_mut<T>(&self) -> Option<FetchMut<T>>
+    pub fn try_fetch_mut<T>(&self) -> Option<FetchMut<'_, T>>
     where
         T: Resource,
     {
         let res_id = ResourceId::new::<T>();
-        let resource = self.resources.get(&res_id)?;
 
-        let borrowed_res = match resource.try_borrow_mut() {
-            Ok(v) => v,
-            Err(e) => panic!("{}: {e}", std::any::type_name::<T>())
-        };
-        Some(FetchMut {
-            inner: AtomicRefMut::map(borrowed_res, Box::as_mut),
-            phantom: PhantomData,
-        })
+        self.cell(&res_id).map(FetchMut::borrow)
     }
 
     /// Like `try_fetch_mut`, but fetches the resource by its `ResourceId` which
@@ -503,16 +571,128 @@ impl World {
     /// # Panics
     ///
     /// This method panics if `id` refers to a different type ID than `T`.
-    pub fn try_fetch_mut_by_id<T>(&self, id: ResourceId) -> Option<FetchMut<T>>
+    pub fn try_fetch_mut_by_id<T>(&self, id: ResourceId) -> Option<FetchMut<'_, T>>
     where
         T: Resource,
     {
         id.assert_same_type_id::<T>();
 
-        self.resources.get(&id).map(|r| FetchMut {
-            inner: AtomicRefMut::map(r.borrow_mut(), Box::as_mut),
-            phantom: PhantomData,
-        })
+        self.cell(&id).map(FetchMut::borrow)
+    }
+
+    /// Returns the ids of all resources of type `R`, including the ones
+    /// inserted with a dynamic id, in no particular order.
+    pub fn resource_ids<R: Resource>(&self) -> impl Iterator<Item = ResourceId> + '_ {
+        let type_id = TypeId::of::<R>();
+
+        self.resources
+            .keys()
+            .filter(move |id| id.type_id == type_id)
+            .cloned()
+    }
+
+    /// Inserts `r` into a new slot, which fetches it without a hash lookup,
+    /// see [`ResourceSlot`].
+    ///
+    /// The resource is only accessible through the returned slot, so it can't
+    /// be fetched by id, e.g. with `Read` or `Write`.
+    pub fn insert_slot<R: Resource>(&mut self, r: R) -> ResourceSlot<R> {
+        let resource = Some(AtomicRefCell::new(Box::new(r) as Box<dyn Resource>));
+        let index = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.id = ResourceId::new::<R>();
+                slot.resource = resource;
+
+                index
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    id: ResourceId::new::<R>(),
+                    resource,
+                });
+
+                self.slots.len() - 1
+            }
+        };
+
+        ResourceSlot::new(index, self.slots[index].generation)
+    }
+
+    /// Removes the resource in `slot` and returns it, or `None` if it has
+    /// already been removed.
+    ///
+    /// This invalidates `slot` and its copies, the storage is reused for the
+    /// next slot inserted.
+    pub fn remove_slot<R: Resource>(&mut self, slot: ResourceSlot<R>) -> Option<R> {
+        self.slot_cell(slot)?;
+        let resolved = &mut self.slots[slot.index];
+        resolved.generation += 1;
+        self.free.push(slot.index);
+
+        resolved
+            .resource
+            .take()
+            .map(AtomicRefCell::into_inner)
+            .map(|x: Box<dyn Resource>| x.downcast())
+            .map(|x: Result<Box<R>, _>| x.ok().unwrap())
+            .map(|x| *x)
+    }
+
+    /// Fetches the resource in `slot` or panics if it doesn't exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource has been removed with `remove_slot`.
+    /// Panics if the resource is being accessed mutably.
+    pub fn fetch_slot<T: Resource>(&self, slot: ResourceSlot<T>) -> Fetch<'_, T> {
+        self.try_fetch_slot(slot).unwrap_or_else(|| fetch_panic!())
+    }
+
+    /// Like `fetch_slot`, but returns `None` if the resource has been
+    /// removed with `remove_slot`.
+    pub fn try_fetch_slot<T: Resource>(&self, slot: ResourceSlot<T>) -> Option<Fetch<'_, T>> {
+        self.slot_cell(slot).map(Fetch::borrow)
+    }
+
+    /// Fetches the resource in `slot` mutably or panics if it doesn't exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource has been removed with `remove_slot`.
+    /// Panics if the resource is already being accessed.
+    pub fn fetch_slot_mut<T: Resource>(&self, slot: ResourceSlot<T>) -> FetchMut<'_, T> {
+        self.try_fetch_slot_mut(slot)
+            .unwrap_or_else(|| fetch_panic!())
+    }
+
+    /// Like `fetch_slot_mut`, but returns `None` if the resource has been
+    /// removed with `remove_slot`.
+    pub fn try_fetch_slot_mut<T: Resource>(
+        &self,
+        slot: ResourceSlot<T>,
+    ) -> Option<FetchMut<'_, T>> {
+        self.slot_cell(slot).map(FetchMut::borrow)
+    }
+
+    #[inline]
+    pub(super) fn cell(&self, id: &ResourceId) -> Option<&AtomicRefCell<Box<dyn Resource>>> {
+        self.resources.get(id)
+    }
+
+    fn slot_cell<T: Resource>(
+        &self,
+        slot: ResourceSlot<T>,
+    ) -> Option<&AtomicRefCell<Box<dyn Resource>>> {
+        let resolved = self.slots.get(slot.index)?;
+        if resolved.generation != slot.generation {
+            return None;
+        }
+        // The slot may come from another `World`.
+        resolved.id.assert_same_type_id::<T>();
+
+        resolved.resource.as_ref()
     }
 
     /// Internal function for inserting resources, should only be used if you
@@ -529,7 +709,93 @@ impl World {
     {
         id.assert_same_type_id::<R>();
 
-        self.resources.insert(id, AtomicRefCell::new(Box::new(r)));
+        self.resources
+            .insert(id.clone(), AtomicRefCell::new(Box::new(r)));
+
+        // hooks need `&mut self`, so they are looked up one at a time
+        let mut index = 0;
+        while let Some(&hook) = self
+            .meta_hooks
+            .get(&TypeId::of::<R>())
+            .and_then(|hooks| hooks.get(index))
+        {
+            hook(self, id.clone());
+            index += 1;
+        }
+    }
+
+    /// Registers every resource `R` in the `MetaTable<T>` of this `World`,
+    /// both the ones already inserted and the ones inserted later using
+    /// [`insert`](World::insert), [`insert_by_id`](World::insert_by_id) or
+    /// [`entry`](World::entry), including the default values inserted when
+    /// setting up systems.
+    ///
+    /// The `MetaTable<T>` is inserted if it doesn't exist yet. Note that
+    /// replacing it with another table drops the registrations made so far.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::{MetaTable, World, cast_from};
+    ///
+    /// trait Named {
+    ///     fn name(&self) -> &str;
+    /// }
+    ///
+    /// cast_from!(dyn Named);
+    ///
+    /// struct Player;
+    ///
+    /// impl Named for Player {
+    ///     fn name(&self) -> &str {
+    ///         "player"
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::empty();
+    /// world.register_meta::<dyn Named, Player>();
+    /// world.insert(Player);
+    ///
+    /// let table = world.fetch::<MetaTable<dyn Named>>();
+    /// assert_eq!(table.iter(&world).next().unwrap().name(), "player");
+    /// ```
+    #[cfg(not(feature = "nightly"))]
+    pub fn register_meta<T, R>(&mut self)
+    where
+        R: Resource,
+        T: CastFrom<R> + ?Sized + 'static,
+    {
+        self.add_meta_hook::<R>(register_in_meta_table::<T, R>);
+    }
+
+    /// Registers every resource `R` in the `MetaTable<T>` of this `World`,
+    /// both the ones already inserted and the ones inserted later using
+    /// [`insert`](World::insert), [`insert_by_id`](World::insert_by_id) or
+    /// [`entry`](World::entry), including the default values inserted when
+    /// setting up systems.
+    ///
+    /// The `MetaTable<T>` is inserted if it doesn't exist yet. Note that
+    /// replacing it with another table drops the registrations made so far.
+    #[cfg(feature = "nightly")]
+    pub fn register_meta<T, R>(&mut self)
+    where
+        R: Resource,
+        T: CastFrom<R> + ?Sized + 'static,
+        T: Pointee<Metadata = DynMetadata<T>>,
+    {
+        self.add_meta_hook::<R>(register_in_meta_table::<T, R>);
+    }
+
+    fn add_meta_hook<R: Resource>(&mut self, hook: MetaHook) {
+        self.meta_hooks
+            .entry(TypeId::of::<R>())
+            .or_default()
+            .push(hook);
+
+        let ids = self.resource_ids::<R>().collect::<Vec<_>>();
+        for id in ids {
+            hook(self, id);
+        }
     }
 
     /// Internal function for removing resources, should only be used if you
@@ -568,7 +834,7 @@ impl World {
         &self,
         id: ResourceId,
     ) -> Option<&AtomicRefCell<Box<dyn Resource>>> {
-        self.resources.get(&id)
+        self.cell(&id)
     }
 
     /// Retrieves a resource without fetching, which is cheaper, but only
@@ -616,6 +882,20 @@ mod tests {
         <Write<Res> as SystemData>::fetch(&world);
     }
 
+    #[test]
+    fn extend_aspects() {
+        type Data<'a> = (Read<'a, Res>, Write<'a, u32>, Option<Read<'a, u64>>, ());
+
+        let mut reads = arrayvec::ArrayVec::<ResourceId, 2>::new();
+        let mut writes = arrayvec::ArrayVec::<ResourceId, 1>::new();
+        Data::extend_reads(&mut reads);
+        Data::extend_writes(&mut writes);
+
+        assert_eq!(&reads[..], &Data::reads()[..]);
+        assert_eq!(&writes[..], &Data::writes()[..]);
+        assert_eq!(&writes[..], &[ResourceId::new::<u32>()]);
+    }
+
     #[test]
     fn fetch_by_id() {
         #![allow(clippy::map_clone)] // False positive
@@ -755,6 +1035,61 @@ mod tests {
         let read: Fetch<Res> = world.fetch();
     }
 
+    #[test]
+    fn slots() {
+        let mut world = World::empty();
+        world.insert(5u32);
+        let slot = world.insert_slot(6u32);
+        let other = world.insert_slot(7u32);
+        assert_ne!(slot, other);
+
+        // slot resources are kept apart from the ones fetched by id
+        *world.fetch_slot_mut(slot) += 1;
+        assert_eq!(*world.fetch::<u32>(), 5);
+        assert_eq!(*world.fetch_slot(slot), 7);
+        assert_eq!(world.resource_ids::<u32>().count(), 1);
+
+        assert_eq!(world.remove_slot(slot), Some(7));
+        assert_eq!(world.remove_slot(slot), None);
+        assert!(world.try_fetch_slot(slot).is_none());
+        assert_eq!(*world.fetch_slot(other), 7);
+
+        // the slot is reused, but the old `ResourceSlot` stays invalid
+        let reused = world.insert_slot(8u64);
+        assert!(world.try_fetch_slot(slot).is_none());
+        assert_eq!(*world.fetch_slot(reused), 8);
+        assert_eq!(world.slots.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Passed a `ResourceId` with a wrong type ID")]
+    fn slot_of_other_world() {
+        let mut world = World::empty();
+        let slot = world.insert_slot(5u32);
+
+        let mut other = World::empty();
+        other.insert_slot(Res);
+        other.fetch_slot(slot);
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "unchecked-fetch",
+        not(debug_assertions),
+        not(feature = "check-borrows")
+    ))]
+    fn unchecked_fetch() {
+        let mut world = World::empty();
+        world.insert(Res);
+
+        // Safety: nothing else borrows `Res`.
+        let unchecked = unsafe { unchecked::fetch(|| world.fetch::<Res>()) };
+        let checked = world.fetch::<Res>();
+
+        assert!(matches!(unchecked.inner, Borrow::Unchecked(_)));
+        assert!(matches!(checked.inner, Borrow::Checked(_)));
+    }
+
     #[test]
     fn remove_insert() {
         let mut world = World::empty();
@@ -763,7 +1098,7 @@ mod tests {
 
         assert!(world.has_value::<Res>());
 
-        println!("{:#?}", world.resources.keys().collect::<Vec<_>>());
+        println!("{:#?}", world.resource_ids::<Res>().collect::<Vec<_>>());
 
         world.remove::<Res>().unwrap();
 
@@ -799,4 +1134,51 @@ mod tests {
         assert!(world.try_fetch::<i32>().is_some());
         assert_eq!(*world.fetch::<i32>(), 33);
     }
+
+    trait Value {
+        fn value(&self) -> i32;
+    }
+
+    crate::cast_from!(dyn Value);
+
+    #[derive(Default)]
+    struct Seven;
+
+    impl Value for Seven {
+        fn value(&self) -> i32 {
+            7
+        }
+    }
+
+    impl Value for i32 {
+        fn value(&self) -> i32 {
+            *self
+        }
+    }
+
+    #[test]
+    fn register_meta() {
+        let mut world = World::empty();
+        world.insert(1i32);
+        world.register_meta::<dyn Value, i32>();
+        world.register_meta::<dyn Value, Seven>();
+
+        world.insert_by_id(ResourceId::new_with_dynamic_id::<i32>(1), 2i32);
+        world.setup::<Read<Seven>>();
+
+        let table = world.fetch::<MetaTable<dyn Value>>();
+        let mut values = table.iter(&world).map(|v| v.value()).collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![1, 2, 7]);
+    }
+
+    #[test]
+    fn register_meta_entry() {
+        let mut world = World::empty();
+        world.register_meta::<dyn Value, Seven>();
+        world.entry().or_insert(Seven);
+
+        let table = world.fetch::<MetaTable<dyn Value>>();
+        assert_eq!(table.iter(&world).map(|v| v.value()).sum::<i32>(), 7);
+    }
 }
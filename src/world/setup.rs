@@ -2,6 +2,12 @@ use crate::{Resource, World};
 
 macro_rules! fetch_panic {
     () => {{
+        #[cfg(feature = "tracing")]
+        tracing::error!(
+            resource = std::any::type_name::<T>(),
+            "tried to fetch a resource which does not exist"
+        );
+
         panic!(
             "\
             Tried to fetch resource of type `{resource_name_simple}`[^1] from the `World`, but \
@@ -23,6 +29,21 @@ macro_rules! fetch_panic {
     }};
 }
 
+macro_rules! borrow_panic {
+    ($e:expr) => {{
+        let e = $e;
+
+        #[cfg(feature = "tracing")]
+        tracing::error!(
+            resource = std::any::type_name::<T>(),
+            "failed to borrow resource: {}",
+            e
+        );
+
+        panic!("{}: {e}", std::any::type_name::<T>())
+    }};
+}
+
 /// A `SetupHandler` that simply uses the default implementation.
 pub struct DefaultProvider;
 
@@ -31,7 +52,10 @@ where
     T: Default + Resource,
 {
     fn setup(world: &mut World) {
-        world.entry().or_insert_with(T::default);
+        // goes through `insert` so the resource is registered in `MetaTable`s
+        if !world.has_value::<T>() {
+            world.insert(T::default());
+        }
     }
 }
 
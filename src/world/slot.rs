@@ -0,0 +1,69 @@
+use std::{fmt, marker::PhantomData};
+
+/// The storage slot of a resource of type `T`, which fetches the resource
+/// with an index instead of a hash lookup.
+///
+/// Slots are returned by [`World::insert_slot`](crate::World::insert_slot)
+/// and stay valid until the resource is removed with
+/// [`World::remove_slot`](crate::World::remove_slot). Resources in slots are
+/// stored apart from the ones identified by `ResourceId`s, so they can only
+/// be fetched through their slot. The storage of a removed resource is reused
+/// for the next inserted one, so fetching an invalidated slot behaves like
+/// fetching a missing resource.
+///
+/// A slot must only be used with the `World` it was obtained from.
+///
+/// ## Examples
+///
+/// ```
+/// use shred::World;
+///
+/// struct Gravity(f32);
+///
+/// let mut world = World::empty();
+/// let slot = world.insert_slot(Gravity(9.81));
+///
+/// world.fetch_slot_mut(slot).0 = 1.62;
+///
+/// assert_eq!(world.fetch_slot(slot).0, 1.62);
+/// ```
+pub struct ResourceSlot<T: ?Sized> {
+    pub(super) generation: usize,
+    pub(super) index: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: ?Sized> ResourceSlot<T> {
+    pub(super) fn new(index: usize, generation: usize) -> Self {
+        ResourceSlot {
+            generation,
+            index,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Clone for ResourceSlot<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for ResourceSlot<T> {}
+
+impl<T: ?Sized> fmt::Debug for ResourceSlot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResourceSlot")
+            .field("index", &self.index)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+impl<T: ?Sized> PartialEq for ResourceSlot<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T: ?Sized> Eq for ResourceSlot<T> {}
@@ -0,0 +1,192 @@
+//! The borrows of fetched resources, which may skip the runtime borrow
+//! checking, see `DispatcherBuilder::with_unchecked_fetch`.
+//!
+//! Fetches are only unchecked while a dispatcher which opted in fetches the
+//! `SystemData` of one of its systems, and only with the `unchecked-fetch`
+//! feature in builds without `debug_assertions` or the `check-borrows`
+//! feature. Other builds only contain the checked borrows.
+
+pub(crate) use self::imp::{Borrow, BorrowMut, fetch};
+
+/// Whether this build supports unchecked fetches.
+pub(crate) const ENABLED: bool = cfg!(all(
+    feature = "unchecked-fetch",
+    not(debug_assertions),
+    not(feature = "check-borrows")
+));
+
+#[cfg(all(
+    feature = "unchecked-fetch",
+    not(debug_assertions),
+    not(feature = "check-borrows")
+))]
+mod imp {
+    use std::cell::Cell;
+
+    use crate::{
+        cell::{AtomicRef, AtomicRefCell, AtomicRefMut},
+        world::Resource,
+    };
+
+    thread_local! {
+        /// Whether fetches on this thread currently skip the borrow flags.
+        static ACTIVE: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// Restores the previous value of `ACTIVE`, even if the fetch panicked.
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            ACTIVE.with(|active| active.set(self.0));
+        }
+    }
+
+    /// Fetches the `SystemData` of a system using `f`, skipping the borrow
+    /// flags.
+    ///
+    /// # Safety
+    ///
+    /// No resource fetched by `f` may be borrowed in a conflicting way until
+    /// the fetched data is dropped.
+    pub unsafe fn fetch<R>(f: impl FnOnce() -> R) -> R {
+        let _restore = Restore(ACTIVE.with(|active| active.replace(true)));
+
+        f()
+    }
+
+    /// A shared borrow of a resource, which only skips the borrow flag if the
+    /// dispatcher guarantees there's no conflicting access.
+    pub enum Borrow<'a> {
+        Checked(AtomicRef<'a, dyn Resource>),
+        Unchecked(&'a dyn Resource),
+    }
+
+    impl<'a> Borrow<'a> {
+        pub fn new<T>(cell: &'a AtomicRefCell<Box<dyn Resource>>) -> Self {
+            if ACTIVE.with(Cell::get) {
+                // Safety: the dispatcher fetching the system data guarantees
+                // that no conflicting borrow exists.
+                Borrow::Unchecked(unsafe { &**cell.as_ptr() })
+            } else {
+                let borrow = cell.try_borrow().unwrap_or_else(|e| borrow_panic!(e));
+
+                Borrow::Checked(AtomicRef::map(borrow, Box::as_ref))
+            }
+        }
+
+        pub fn get(&self) -> &dyn Resource {
+            match *self {
+                Borrow::Checked(ref borrow) => &**borrow,
+                Borrow::Unchecked(resource) => resource,
+            }
+        }
+    }
+
+    impl Clone for Borrow<'_> {
+        fn clone(&self) -> Self {
+            match *self {
+                Borrow::Checked(ref borrow) => Borrow::Checked(AtomicRef::clone(borrow)),
+                Borrow::Unchecked(resource) => Borrow::Unchecked(resource),
+            }
+        }
+    }
+
+    /// A mutable borrow of a resource, which only skips the borrow flag if
+    /// the dispatcher guarantees there's no conflicting access.
+    pub enum BorrowMut<'a> {
+        Checked(AtomicRefMut<'a, dyn Resource>),
+        Unchecked(&'a mut dyn Resource),
+    }
+
+    impl<'a> BorrowMut<'a> {
+        pub fn new<T>(cell: &'a AtomicRefCell<Box<dyn Resource>>) -> Self {
+            if ACTIVE.with(Cell::get) {
+                // Safety: the dispatcher fetching the system data guarantees
+                // that no conflicting borrow exists.
+                BorrowMut::Unchecked(unsafe { &mut **cell.as_ptr() })
+            } else {
+                let borrow = cell.try_borrow_mut().unwrap_or_else(|e| borrow_panic!(e));
+
+                BorrowMut::Checked(AtomicRefMut::map(borrow, Box::as_mut))
+            }
+        }
+
+        pub fn get(&self) -> &dyn Resource {
+            match *self {
+                BorrowMut::Checked(ref borrow) => &**borrow,
+                BorrowMut::Unchecked(ref resource) => *resource,
+            }
+        }
+
+        pub fn get_mut(&mut self) -> &mut dyn Resource {
+            match *self {
+                BorrowMut::Checked(ref mut borrow) => &mut **borrow,
+                BorrowMut::Unchecked(ref mut resource) => *resource,
+            }
+        }
+    }
+}
+
+#[cfg(not(all(
+    feature = "unchecked-fetch",
+    not(debug_assertions),
+    not(feature = "check-borrows")
+)))]
+mod imp {
+    use crate::{
+        cell::{AtomicRef, AtomicRefCell, AtomicRefMut},
+        world::Resource,
+    };
+
+    /// Fetches the `SystemData` of a system using `f`, which checks every
+    /// borrow in this build.
+    ///
+    /// # Safety
+    ///
+    /// No resource fetched by `f` may be borrowed in a conflicting way until
+    /// the fetched data is dropped.
+    pub unsafe fn fetch<R>(f: impl FnOnce() -> R) -> R {
+        f()
+    }
+
+    /// A shared borrow of a resource.
+    pub struct Borrow<'a>(AtomicRef<'a, dyn Resource>);
+
+    impl<'a> Borrow<'a> {
+        pub fn new<T>(cell: &'a AtomicRefCell<Box<dyn Resource>>) -> Self {
+            let borrow = cell.try_borrow().unwrap_or_else(|e| borrow_panic!(e));
+
+            Borrow(AtomicRef::map(borrow, Box::as_ref))
+        }
+
+        pub fn get(&self) -> &dyn Resource {
+            &*self.0
+        }
+    }
+
+    impl Clone for Borrow<'_> {
+        fn clone(&self) -> Self {
+            Borrow(AtomicRef::clone(&self.0))
+        }
+    }
+
+    /// A mutable borrow of a resource.
+    pub struct BorrowMut<'a>(AtomicRefMut<'a, dyn Resource>);
+
+    impl<'a> BorrowMut<'a> {
+        pub fn new<T>(cell: &'a AtomicRefCell<Box<dyn Resource>>) -> Self {
+            let borrow = cell.try_borrow_mut().unwrap_or_else(|e| borrow_panic!(e));
+
+            BorrowMut(AtomicRefMut::map(borrow, Box::as_mut))
+        }
+
+        pub fn get(&self) -> &dyn Resource {
+            &*self.0
+        }
+
+        pub fn get_mut(&mut self) -> &mut dyn Resource {
+            &mut *self.0
+        }
+    }
+}
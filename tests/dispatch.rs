@@ -268,3 +268,821 @@ fn dispatch_stage_group() {
 
     d.dispatch(&world);
 }
+
+#[test]
+fn dispatch_trace_recorder() {
+    use std::sync::Arc;
+
+    use shred::{MultiDispatchController, MultiDispatcher, TraceRecorder};
+
+    struct Thrice;
+
+    impl<'a> MultiDispatchController<'a> for Thrice {
+        type SystemData = ();
+
+        fn plan(&mut self, _: ()) -> usize {
+            3
+        }
+    }
+
+    let recorder = Arc::new(TraceRecorder::new());
+    let mut world = World::empty();
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(DummySys, "read_a", &[])
+        .with_batch(
+            MultiDispatcher::new(Thrice),
+            DispatcherBuilder::new().with(DummySysMut, "write_a", &[]),
+            "batch",
+            &["read_a"],
+        )
+        .with_thread_local(DummySys)
+        .with_trace_recorder(recorder.clone())
+        .build();
+    d.setup(&mut world);
+
+    d.dispatch(&world);
+    d.dispatch(&world);
+
+    // per dispatch: the dispatch span, 3 systems (read_a, batch and the thread
+    // local) and 3 batch iterations with one system each
+    assert_eq!(recorder.len(), 2 * 10);
+
+    let mut json = Vec::new();
+    recorder.write_json(&mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+
+    assert!(json.contains("\"name\":\"read_a\",\"cat\":\"system\""));
+    assert!(json.contains("\"name\":\"batch\",\"cat\":\"batch\""));
+    assert!(json.contains("\"name\":\"DummySys\",\"cat\":\"thread_local\""));
+    assert!(json.contains("\"args\":{\"frame\":2,\"stage\":1}"));
+    // the batch iterations are part of the frame of the parent dispatch
+    assert_eq!(json.matches("\"frame\":1").count(), 10);
+    assert_eq!(json.matches("\"frame\":2").count(), 10);
+}
+
+#[test]
+fn dispatch_observer() {
+    use std::sync::{Arc, Mutex};
+
+    use shred::{DispatchObserver, SystemId};
+
+    #[derive(Default)]
+    struct Log(Mutex<Vec<String>>);
+
+    impl DispatchObserver for Log {
+        fn dispatch_start(&self) {
+            self.0.lock().unwrap().push("dispatch_start".to_owned());
+        }
+
+        fn dispatch_end(&self) {
+            self.0.lock().unwrap().push("dispatch_end".to_owned());
+        }
+
+        fn stage_start(&self, stage: usize) {
+            self.0.lock().unwrap().push(format!("stage_start {}", stage));
+        }
+
+        fn stage_end(&self, stage: usize) {
+            self.0.lock().unwrap().push(format!("stage_end {}", stage));
+        }
+
+        fn before_system(&self, name: &str, id: SystemId) {
+            self.0.lock().unwrap().push(format!("before {} {}", name, id.0));
+        }
+
+        fn after_system(&self, name: &str, id: SystemId) {
+            self.0.lock().unwrap().push(format!("after {} {}", name, id.0));
+        }
+    }
+
+    let log = Arc::new(Log::default());
+    let mut world = World::empty();
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(DummySys, "read_a", &[])
+        .with(DummySysMut, "write_a", &["read_a"])
+        .with_thread_local(DummySys)
+        .with_observer(log.clone())
+        .build();
+    d.setup(&mut world);
+
+    d.dispatch(&world);
+
+    assert_eq!(
+        *log.0.lock().unwrap(),
+        vec![
+            "dispatch_start",
+            "stage_start 0",
+            "before read_a 0",
+            "after read_a 0",
+            "stage_end 0",
+            "stage_start 1",
+            "before write_a 1",
+            "after write_a 1",
+            "stage_end 1",
+            "dispatch_end",
+            "before DummySys 2",
+            "after DummySys 2",
+        ]
+    );
+}
+
+#[test]
+fn dispatch_try_system() {
+    use std::sync::{Arc, Mutex};
+
+    use shred::{ErrorHandler, TrySystem};
+
+    struct Fail(&'static str);
+
+    impl<'a> TrySystem<'a> for Fail {
+        type Error = String;
+        type SystemData = Read<'a, Res>;
+
+        fn run(&mut self, _: Self::SystemData) -> Result<(), String> {
+            Err(self.0.to_owned())
+        }
+    }
+
+    let mut world = World::empty();
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with_try(Fail("first"), "first", &[])
+        .with_try(Fail("second"), "", &[])
+        .build();
+    d.setup(&mut world);
+
+    d.dispatch(&world);
+
+    let mut errors = d
+        .errors()
+        .errors()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    errors.sort();
+    assert_eq!(
+        errors,
+        [
+            "System `Fail` failed: second",
+            "System `first` failed: first",
+        ]
+    );
+
+    let handled = Arc::new(Mutex::new(Vec::new()));
+    let sink = handled.clone();
+    world.insert(ErrorHandler::new(move |e| {
+        sink.lock().unwrap().push(e.name().to_owned())
+    }));
+
+    d.dispatch(&world);
+
+    assert!(d.errors().is_empty());
+    assert_eq!(handled.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn dispatch_labels() {
+    use std::sync::{Arc, Mutex};
+
+    use shred::{DispatchObserver, SystemId};
+
+    /// Records the stage each system ran in.
+    #[derive(Default)]
+    struct Stages(Mutex<(usize, Vec<(String, usize)>)>);
+
+    impl DispatchObserver for Stages {
+        fn stage_start(&self, stage: usize) {
+            self.0.lock().unwrap().0 = stage;
+        }
+
+        fn after_system(&self, name: &str, _: SystemId) {
+            let mut stages = self.0.lock().unwrap();
+            let stage = stages.0;
+            stages.1.push((name.to_owned(), stage));
+        }
+    }
+
+    let stages = Arc::new(Stages::default());
+    let mut world = World::empty();
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with_labeled(DummySys, "keyboard", &["input"], &[])
+        .with_labeled(DummySys, "gamepad", &["input"], &[])
+        .with_set_order("input", "physics")
+        .with_labeled(DummySys, "collision", &["physics"], &[])
+        .with(DummySys, "render", &["physics"])
+        .with_observer(stages.clone())
+        .build();
+    d.setup(&mut world);
+    d.dispatch_seq(&world);
+
+    let mut stages = stages.0.lock().unwrap().1.clone();
+    stages.sort();
+    assert_eq!(
+        stages,
+        [
+            ("collision".to_owned(), 1),
+            ("gamepad".to_owned(), 0),
+            ("keyboard".to_owned(), 0),
+            ("render".to_owned(), 2),
+        ]
+    );
+}
+
+#[test]
+fn dispatch_order() {
+    use shred::BuildError;
+
+    let builder = DispatcherBuilder::new()
+        .with(DummySys, "b", &["a"])
+        .with(DummySys, "a", &[])
+        .with(DummySys, "c", &[])
+        .with_order("c", "a")
+        .with_order("c", "optional");
+    assert_eq!(
+        format!("{:?}", builder),
+        "seq![\n\tpar![\n\t\tseq![\n\t\t\tc,\n\t\t],\n\t],\n\tpar![\n\t\tseq![\n\t\t\ta,\n\t\t],\n\t],\n\tpar![\n\t\tseq![\n\t\t\tb,\n\t\t],\n\t],\n]\n"
+    );
+
+    let error = DispatcherBuilder::new()
+        .with(DummySys, "a", &["c"])
+        .with(DummySys, "b", &["a"])
+        .with(DummySys, "c", &["b"])
+        .try_build()
+        .err()
+        .unwrap();
+    assert_eq!(
+        error,
+        BuildError::Cycle(vec![
+            "a".to_owned(),
+            "b".to_owned(),
+            "c".to_owned(),
+            "a".to_owned()
+        ])
+    );
+    assert_eq!(
+        error.to_string(),
+        "Systems depend on each other: a -> b -> c -> a"
+    );
+}
+
+#[test]
+fn dispatch_order_self() {
+    use shred::BuildError;
+
+    let cycle = BuildError::Cycle(vec!["a".to_owned(), "a".to_owned()]);
+
+    let error = DispatcherBuilder::new()
+        .with(DummySys, "a", &["a"])
+        .try_build()
+        .err();
+    assert_eq!(error, Some(cycle.clone()));
+
+    let error = DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with_order("a", "a")
+        .try_build()
+        .err();
+    assert_eq!(error, Some(cycle));
+
+    // a system in both ordered sets doesn't wait for itself
+    let builder = DispatcherBuilder::new()
+        .with_labeled(DummySys, "a", &["input", "physics"], &[])
+        .with_labeled(DummySys, "b", &["physics"], &[])
+        .with_set_order("input", "physics");
+    assert_eq!(
+        format!("{:?}", builder),
+        "seq![\n\tpar![\n\t\tseq![\n\t\t\ta,\n\t\t],\n\t],\n\tpar![\n\t\tseq![\n\t\t\tb,\n\t\t],\n\t],\n]\n"
+    );
+    builder.build();
+}
+
+#[test]
+fn dispatch_builder_edit() {
+    use shred::{BuildError, SystemId, TrySystem};
+
+    struct Fail;
+
+    impl<'a> TrySystem<'a> for Fail {
+        type Error = String;
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) -> Result<(), String> {
+            Err("mod failed".to_owned())
+        }
+    }
+
+    let mut builder = DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with(DummySys, "net", &[])
+        .with(DummySys, "b", &["a"]);
+    builder.replace("net", DummySysMut);
+    assert!(builder.remove("b"));
+    assert!(!builder.remove("b"));
+    assert_eq!(
+        format!("{:?}", builder),
+        "seq![\n\tpar![\n\t\tseq![\n\t\t\ta,\n\t\t],\n\t],\n\tpar![\n\t\tseq![\n\t\t\tnet,\n\t\t],\n\t],\n]\n"
+    );
+
+    let other = DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with(DummySys, "net", &[]);
+    assert_eq!(
+        builder.merge(other),
+        Err(BuildError::DuplicateNames(vec![
+            "a".to_owned(),
+            "net".to_owned()
+        ]))
+    );
+
+    let other = DispatcherBuilder::new()
+        .with_try(Fail, "mod", &["net"])
+        .with_order("mod", "a");
+    builder.merge(other).unwrap();
+    assert!(builder.has_system("mod"));
+
+    let mut world = World::empty();
+    let mut d: Dispatcher = builder.build();
+    d.setup(&mut world);
+    d.dispatch(&world);
+
+    assert_eq!(d.errors().errors().len(), 1);
+    assert_eq!(d.errors().errors()[0].name(), "mod");
+    // "mod" comes after "a", "net" and the removed "b"
+    assert_eq!(d.errors().errors()[0].id(), SystemId(3));
+}
+
+#[test]
+fn dispatch_insert_remove_system() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use shred::BuildError;
+
+    struct Count {
+        runs: usize,
+        seen: Arc<AtomicUsize>,
+    }
+
+    impl<'a> System<'a> for Count {
+        type SystemData = Write<'a, Res>;
+
+        fn run(&mut self, _: Self::SystemData) {
+            self.runs += 1;
+            self.seen.store(self.runs, Ordering::SeqCst);
+        }
+
+        fn dispose(self, world: &mut World) {
+            world.insert(ResB);
+        }
+    }
+
+    let count = Arc::new(AtomicUsize::new(0));
+    let script = Arc::new(AtomicUsize::new(0));
+    let mut world = World::empty();
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(
+            Count {
+                runs: 0,
+                seen: count.clone(),
+            },
+            "count",
+            &[],
+        )
+        .build();
+    d.setup(&mut world);
+    d.dispatch(&world);
+
+    let system = Count {
+        runs: 0,
+        seen: script.clone(),
+    };
+    d.insert_system(&mut world, system, "script", &["count"]).unwrap();
+    d.dispatch(&world);
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+    assert_eq!(script.load(Ordering::SeqCst), 1);
+
+    assert_eq!(
+        d.insert_system(&mut world, DummySys, "script", &[]),
+        Err(BuildError::DuplicateNames(vec!["script".to_owned()]))
+    );
+    assert_eq!(
+        d.insert_system(&mut world, DummySys, "other", &["missing"]),
+        Err(BuildError::UnknownDependency {
+            system: "other".to_owned(),
+            dependency: "missing".to_owned(),
+        })
+    );
+
+    assert!(d.remove_system(&mut world, "count"));
+    assert!(!d.remove_system(&mut world, "count"));
+    assert!(world.has_value::<ResB>());
+
+    d.dispatch(&world);
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+    assert_eq!(script.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn dispatch_insert_system_panicking_setup() {
+    use std::panic::{self, AssertUnwindSafe};
+
+    struct BadSetup;
+
+    impl<'a> System<'a> for BadSetup {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {}
+
+        fn setup(&mut self, _: &mut World) {
+            panic!("setup failed");
+        }
+    }
+
+    let mut world = World::empty();
+    let mut d: Dispatcher = DispatcherBuilder::new().with(DummySys, "a", &[]).build();
+    d.setup(&mut world);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        d.insert_system(&mut world, BadSetup, "bad", &["a"])
+    }));
+    assert!(result.is_err());
+
+    // the name is free again and the dispatcher still runs
+    d.dispatch(&world);
+    d.insert_system(&mut world, DummySys, "bad", &["a"]).unwrap();
+    d.dispatch(&world);
+}
+
+#[test]
+fn dispatch_system_access() {
+    use std::any::Any;
+
+    use shred::TrySystem;
+
+    struct Tuning(u32);
+
+    impl<'a> TrySystem<'a> for Tuning {
+        type Error = String;
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn as_any(&self) -> Option<&dyn Any> {
+            Some(self)
+        }
+
+        fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+            Some(self)
+        }
+    }
+
+    struct Local(u32);
+
+    impl<'a> System<'a> for Local {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {}
+
+        fn as_any(&self) -> Option<&dyn Any> {
+            Some(self)
+        }
+
+        fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+            Some(self)
+        }
+    }
+
+    struct Plain(u32);
+
+    impl<'a> System<'a> for Plain {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {}
+    }
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(DummySys, "dummy", &[])
+        .with_accessible(Plain(1), "plain", &[])
+        .with_try(Tuning(1), "tuning", &[])
+        .with_thread_local(DummySys)
+        .with_thread_local(Local(1))
+        .with_thread_local_accessible(Plain(1))
+        .build();
+
+    d.system_mut::<Tuning>("tuning").unwrap().0 = 2;
+    assert_eq!(d.system::<Tuning>("tuning").map(|t| t.0), Some(2));
+    assert!(d.system::<DummySys>("tuning").is_none());
+    assert!(d.system::<Tuning>("missing").is_none());
+
+    // systems added with `with` are only accessible if they implement `as_any`
+    assert!(d.system::<DummySys>("dummy").is_none());
+    d.system_mut::<Plain>("plain").unwrap().0 = 2;
+    assert_eq!(d.system::<Plain>("plain").map(|p| p.0), Some(2));
+
+    d.thread_local_system_mut::<Local>().unwrap().0 = 2;
+    assert_eq!(d.thread_local_system::<Local>().map(|l| l.0), Some(2));
+    assert!(d.thread_local_system::<Tuning>().is_none());
+    d.thread_local_system_mut::<Plain>().unwrap().0 = 3;
+    assert_eq!(d.thread_local_system::<Plain>().map(|p| p.0), Some(3));
+}
+
+#[test]
+#[cfg(feature = "unchecked-fetch")]
+fn dispatch_unchecked_fetch() {
+    struct Count;
+
+    impl<'a> System<'a> for Count {
+        type SystemData = (Read<'a, Res>, Write<'a, u32>);
+
+        fn run(&mut self, (_, mut count): Self::SystemData) {
+            *count += 1;
+        }
+    }
+
+    let mut world = World::empty();
+    world.insert(Res);
+
+    // Safety: the systems only fetch their declared system data and nothing
+    // else borrows the world while dispatching.
+    let mut d: Dispatcher = unsafe {
+        DispatcherBuilder::new()
+            .with(Count, "count", &[])
+            .with(DummySys, "dummy", &[])
+            .with_unchecked_fetch()
+    }
+    .build();
+    d.setup(&mut world);
+
+    d.dispatch(&world);
+    d.dispatch(&world);
+
+    assert_eq!(*world.fetch::<u32>(), 2);
+    // the resources aren't borrowed anymore
+    *world.fetch_mut::<Res>() = Res;
+}
+
+#[test]
+#[cfg(all(
+    feature = "unchecked-fetch",
+    any(debug_assertions, feature = "check-borrows")
+))]
+#[should_panic(expected = "already borrowed")]
+fn dispatch_unchecked_fetch_checked_in_debug() {
+    struct Conflicting;
+
+    impl<'a> System<'a> for Conflicting {
+        type SystemData = (Read<'a, Res>, Write<'a, Res>);
+
+        fn run(&mut self, _: Self::SystemData) {}
+    }
+
+    let mut world = World::empty();
+    world.insert(Res);
+
+    // Safety: violated on purpose, but every borrow is still checked in
+    // debug builds.
+    let mut d: Dispatcher = unsafe {
+        DispatcherBuilder::new()
+            .with(Conflicting, "conflicting", &[])
+            .with_unchecked_fetch()
+    }
+    .build();
+
+    d.dispatch(&world);
+}
+
+#[test]
+#[cfg(all(feature = "parallel", feature = "unchecked-fetch"))]
+#[should_panic(expected = "already borrowed")]
+fn dispatch_unchecked_fetch_nested_run_now() {
+    use shred::RunNow;
+
+    #[derive(Default)]
+    struct Held;
+
+    struct WriteHeld;
+
+    impl<'a> System<'a> for WriteHeld {
+        type SystemData = Write<'a, Held>;
+
+        fn run(&mut self, _: Self::SystemData) {}
+    }
+
+    struct Nested<'w> {
+        world: &'w World,
+    }
+
+    impl<'a> System<'a> for Nested<'_> {
+        type SystemData = Read<'a, Res>;
+
+        fn run(&mut self, _: Self::SystemData) {
+            WriteHeld.run_now(self.world);
+        }
+    }
+
+    let mut world = World::empty();
+    world.insert(Res);
+    world.insert(Held);
+
+    {
+        let world = &world;
+        // Safety: `Held` isn't declared by any system, so only the nested
+        // fetch conflicts with the borrow below, which is checked.
+        let mut d: Dispatcher = unsafe {
+            DispatcherBuilder::new()
+                .with(Nested { world }, "nested", &[])
+                .with_unchecked_fetch()
+        }
+        .build();
+
+        let _held = world.fetch_mut::<Held>();
+        d.dispatch(world);
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn dispatch_concurrent() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use shred::Concurrent;
+
+    struct Increment;
+
+    impl<'a> System<'a> for Increment {
+        type SystemData = Concurrent<'a, AtomicU32>;
+
+        fn run(&mut self, count: Self::SystemData) {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    struct Reset;
+
+    impl<'a> System<'a> for Reset {
+        type SystemData = Write<'a, AtomicU32>;
+
+        fn run(&mut self, mut count: Self::SystemData) {
+            *count.get_mut() = 0;
+        }
+    }
+
+    let mut world = World::empty();
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Increment, "a", &[])
+        .with(Increment, "b", &[])
+        .build();
+    d.setup(&mut world);
+
+    assert_eq!(d.max_threads(), 2);
+    d.dispatch(&world);
+    assert_eq!(world.fetch::<AtomicU32>().load(Ordering::Relaxed), 2);
+
+    let d: Dispatcher = DispatcherBuilder::new()
+        .with(Increment, "a", &[])
+        .with(Reset, "reset", &[])
+        .build();
+    assert_eq!(d.max_threads(), 1);
+}
+
+#[test]
+fn dispatch_accumulate() {
+    use std::sync::{Arc, Mutex};
+
+    use shred::{Accumulate, RunNow};
+
+    struct Add(u64);
+
+    impl<'a> System<'a> for Add {
+        type SystemData = Accumulate<'a, u64>;
+
+        fn run(&mut self, mut sum: Self::SystemData) {
+            *sum += self.0;
+        }
+    }
+
+    struct Observe(Arc<Mutex<Vec<u64>>>);
+
+    impl<'a> System<'a> for Observe {
+        type SystemData = Read<'a, u64>;
+
+        fn run(&mut self, sum: Self::SystemData) {
+            self.0.lock().unwrap().push(*sum);
+        }
+    }
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+
+    let mut world = World::empty();
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Add(1), "a", &[])
+        .with(Add(2), "b", &[])
+        .with(Observe(seen.clone()), "observe", &[])
+        .with(Add(4), "c", &[])
+        .build();
+    d.setup(&mut world);
+
+    #[cfg(feature = "parallel")]
+    assert_eq!(d.max_threads(), 3);
+
+    d.dispatch(&world);
+    assert_eq!(*world.fetch::<u64>(), 7);
+    d.dispatch(&world);
+    assert_eq!(*world.fetch::<u64>(), 14);
+    assert_eq!(*seen.lock().unwrap(), vec![7, 14]);
+
+    Add(10).run_now(&world);
+    assert_eq!(*world.fetch::<u64>(), 24);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn dispatch_accumulate_nested_run_now() {
+    use std::sync::{Arc, Mutex};
+
+    use shred::{Accumulate, RunNow};
+
+    struct Add(u64);
+
+    impl<'a> System<'a> for Add {
+        type SystemData = Accumulate<'a, u64>;
+
+        fn run(&mut self, mut sum: Self::SystemData) {
+            *sum += self.0;
+        }
+    }
+
+    struct Nested<'w> {
+        seen: Arc<Mutex<Vec<u64>>>,
+        world: &'w World,
+    }
+
+    impl<'a> System<'a> for Nested<'_> {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {
+            Add(10).run_now(self.world);
+            self.seen.lock().unwrap().push(*self.world.fetch::<u64>());
+        }
+    }
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+
+    let mut world = World::empty();
+    RunNow::setup(&mut Add(0), &mut world);
+
+    {
+        let world = &world;
+        let mut d: Dispatcher = DispatcherBuilder::new()
+            .with(
+                Nested {
+                    seen: seen.clone(),
+                    world,
+                },
+                "nested",
+                &[],
+            )
+            .build();
+
+        d.dispatch(world);
+    }
+
+    // the nested system merged right after it ran, not after the stage
+    assert_eq!(*seen.lock().unwrap(), vec![10]);
+    assert_eq!(*world.fetch::<u64>(), 10);
+}
+
+#[test]
+fn dispatch_accumulate_panicking() {
+    use shred::{Accumulate, PanicPolicy};
+
+    struct Add(u64, bool);
+
+    impl<'a> System<'a> for Add {
+        type SystemData = Accumulate<'a, u64>;
+
+        fn run(&mut self, mut sum: Self::SystemData) {
+            *sum += self.0;
+            if self.1 {
+                panic!("lost the receipt");
+            }
+        }
+    }
+
+    let mut world = World::empty();
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Add(1, false), "a", &[])
+        .with(Add(2, true), "b", &[])
+        .with_panic_policy(PanicPolicy::Continue)
+        .build();
+    d.setup(&mut world);
+
+    d.dispatch(&world);
+
+    // the value of the panicking system is discarded
+    assert_eq!(d.errors().panics().len(), 1);
+    assert_eq!(*world.fetch::<u64>(), 1);
+}
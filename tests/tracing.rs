@@ -0,0 +1,111 @@
+#![cfg(feature = "tracing")]
+
+use std::{
+    fmt::Debug,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use shred::{DispatcherBuilder, Read, ReadExpect, System, World};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+
+#[derive(Clone, Default)]
+struct Recorder {
+    events: Arc<Mutex<Vec<String>>>,
+    next_id: Arc<AtomicU64>,
+    spans: Arc<Mutex<Vec<String>>>,
+}
+
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0 += &format!(" {}={:?}", field.name(), value);
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields(span.metadata().name().to_owned());
+        span.record(&mut fields);
+        self.spans.lock().unwrap().push(fields.0);
+
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields(String::new());
+        event.record(&mut fields);
+        self.events.lock().unwrap().push(fields.0);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[derive(Default)]
+struct Res;
+
+struct Missing;
+
+struct ReadRes;
+
+impl<'a> System<'a> for ReadRes {
+    type SystemData = Read<'a, Res>;
+
+    fn run(&mut self, _: Self::SystemData) {}
+}
+
+struct ReadMissing;
+
+impl<'a> System<'a> for ReadMissing {
+    type SystemData = ReadExpect<'a, Missing>;
+
+    fn run(&mut self, _: Self::SystemData) {}
+}
+
+#[test]
+fn spans_and_fetch_panic_events() {
+    let recorder = Recorder::default();
+    tracing::subscriber::set_global_default(recorder.clone()).unwrap();
+
+    let mut world = World::empty();
+    let mut dispatcher = DispatcherBuilder::new()
+        .with(ReadRes, "read_res", &[])
+        .with_thread_local(ReadRes)
+        .build();
+    dispatcher.setup(&mut world);
+    dispatcher.dispatch(&world);
+
+    {
+        let spans = recorder.spans.lock().unwrap();
+        assert!(spans.contains(&"system name=\"read_res\" stage=0 group=0".to_owned()));
+        assert!(spans.contains(&"thread_local name=\"ReadRes\"".to_owned()));
+    }
+
+    let mut dispatcher = DispatcherBuilder::new()
+        .with(ReadMissing, "read_missing", &[])
+        .build();
+    let result = catch_unwind(AssertUnwindSafe(|| dispatcher.dispatch(&world)));
+    assert!(result.is_err());
+
+    let events = recorder.events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert!(events[0].contains("resource=\"tracing::Missing\""));
+}